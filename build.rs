@@ -0,0 +1,18 @@
+use std::process::Command;
+
+fn main() {
+    // Code version recorded in manifests, marked dirty if there were uncommitted changes.
+    let output = Command::new("git")
+        .args(["describe", "--always", "--dirty"])
+        .output();
+    if let Ok(output) = output {
+        if output.status.success() {
+            let commit = String::from_utf8_lossy(&output.stdout);
+            println!("cargo:rustc-env=GIT_COMMIT={}", commit.trim());
+        }
+    }
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    println!("cargo:rerun-if-changed=.git/index");
+    println!("cargo:rerun-if-changed=src");
+}
@@ -0,0 +1,204 @@
+use crate::trader::{Equity, Freshness, Position, Signal};
+use chrono::{DateTime, Utc};
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use serde_json::{to_value, Value};
+use std::{
+    collections::HashSet,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, RwLock,
+    },
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenPosition {
+    #[serde(flatten)]
+    pub position: Position,
+    pub unrealized_profit: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WalletStatus {
+    pub balance: Decimal,
+    pub parts: usize,
+    pub borrowed: usize,
+    pub equity: Option<Equity>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Status {
+    pub time: Option<DateTime<Utc>>,
+    pub positions: Vec<OpenPosition>,
+    pub wallet: Option<WalletStatus>,
+    pub signals: Vec<Signal>,
+    pub freshness: Vec<Freshness>,
+    pub trades: Vec<Position>,
+}
+
+impl Status {
+    fn route(&self, path: &str) -> Option<serde_json::Result<Value>> {
+        let value = match path {
+            "/status" => to_value(self),
+            "/positions" => to_value(&self.positions),
+            "/wallet" => to_value(&self.wallet),
+            "/signals" => to_value(&self.signals),
+            "/freshness" => to_value(&self.freshness),
+            "/trades" => to_value(&self.trades),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+// Indices of the positions published as trades, all before `first` and those in `later`.
+#[derive(Default)]
+struct Published {
+    first: usize,
+    later: HashSet<usize>,
+}
+
+impl Published {
+    // Closed positions not published yet, only looking at those since the first open one.
+    fn next<'a>(&mut self, positions: &'a [Position]) -> Vec<&'a Position> {
+        let mut next = Vec::new();
+        for (i, position) in positions.iter().enumerate().skip(self.first) {
+            if position.is_closed() && self.later.insert(i) {
+                next.push(position);
+            }
+        }
+        while self.later.remove(&self.first) {
+            self.first += 1;
+        }
+        next
+    }
+}
+
+// Latest state published by the trader, only updated while the server runs.
+pub struct Api {
+    enabled: AtomicBool,
+    status: RwLock<Status>,
+    published: Mutex<Published>,
+}
+
+impl Api {
+    pub fn new() -> Self {
+        Api {
+            enabled: AtomicBool::new(false),
+            status: RwLock::new(Status::default()),
+            published: Mutex::new(Published::default()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    // Replaces the status, but appends the trades closed since the last one, so every tick only
+    // copies those.
+    pub fn publish(&self, mut status: Status, positions: &[Position]) {
+        let next = self.published.lock().unwrap().next(positions);
+        let mut current = self.status.write().unwrap();
+        status.trades = std::mem::take(&mut current.trades);
+        status.trades.extend(next.into_iter().cloned());
+        *current = status;
+    }
+}
+
+async fn handle(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let value = match req.method() {
+        &Method::GET => crate::API.status.read().unwrap().route(req.uri().path()),
+        _ => None,
+    };
+    let response = match value {
+        Some(Ok(value)) => Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(value.to_string())),
+        Some(Err(err)) => Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::from(err.to_string())),
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    };
+    Ok(response.unwrap())
+}
+
+pub async fn serve(addr: SocketAddr) {
+    let server = match Server::try_bind(&addr) {
+        Ok(server) => server,
+        Err(err) => {
+            log::error!("Could not serve API on {}: {}", addr, err);
+            return;
+        }
+    };
+    crate::API.enabled.store(true, Ordering::Relaxed);
+    log::info!("Serving API on http://{}.", addr);
+    let service = make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(handle)) });
+    if let Err(err) = server.serve(service).await {
+        log::error!("API server failed: {}", err);
+    }
+    crate::API.enabled.store(false, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trader::{testing, Coin};
+
+    #[test]
+    fn route() {
+        let status = Status {
+            positions: vec![OpenPosition {
+                position: Position::new(Coin::BTC, Coin::ETH, Decimal::new(5, 2)),
+                unrealized_profit: Decimal::new(-12, 1),
+            }],
+            ..Status::default()
+        };
+
+        let positions = status.route("/positions").unwrap().unwrap();
+        assert_eq!(positions[0]["long"], "BTC");
+        assert_eq!(positions[0]["unrealized_profit"], "-1.2");
+        assert_eq!(
+            status.route("/trades").unwrap().unwrap(),
+            Value::Array(vec![])
+        );
+        assert!(status.route("/status").unwrap().unwrap()["wallet"].is_null());
+        assert!(status.route("/unknown").is_none());
+    }
+
+    #[test]
+    fn published() {
+        let time = Utc::now();
+        let prices = testing::prices(10, 10, time);
+        let position = |long, short| {
+            let mut position = Position::new(long, short, Decimal::new(5, 2));
+            position.open(&prices, Decimal::new(20, 0));
+            position
+        };
+        let mut positions = vec![
+            position(Coin::BTC, Coin::ETH),
+            position(Coin::ETH, Coin::BTC),
+            position(Coin::BTC, Coin::ETH),
+        ];
+        let mut published = Published::default();
+        assert!(published.next(&positions).is_empty());
+
+        positions[1].close(&prices);
+        assert_eq!(published.next(&positions).len(), 1);
+        assert!(published.next(&positions).is_empty());
+        assert_eq!(published.first, 0);
+
+        positions[0].close(&prices);
+        assert_eq!(published.next(&positions).len(), 1);
+        assert_eq!(published.first, 2);
+        assert!(published.later.is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{
+    recorder::Series,
+    trader::{Coin, Params, Position},
+    Number,
+};
+use chrono::{DateTime, Utc};
+use plotters::prelude::*;
+use std::{error::Error, path::Path};
+
+// Plots the spread of a recorded pair against its entry thresholds, marking opened and closed positions.
+pub fn plot_pair<P: AsRef<Path>, Q: AsRef<Path>>(
+    series: P,
+    long: Coin,
+    short: Coin,
+    params: &Params,
+    positions: &[Position],
+    out: Q,
+) -> Result<(), Box<dyn Error>> {
+    // Columns are corr, diff, stdev, threshold and enter.
+    let rows = Series::read(series)?;
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let from = rows.first().unwrap().0;
+    let to = rows.last().unwrap().0;
+    let (min, max) = rows
+        .iter()
+        .fold((-params.max_diff, params.max_diff), |(min, max), (_, v)| {
+            (min.min(v[1]), max.max(v[1].max(v[3])))
+        });
+    let diff_at = |time: DateTime<Utc>| -> Number {
+        let i = rows.partition_point(|(t, _)| *t < time).min(rows.len() - 1);
+        rows[i].1[1]
+    };
+
+    let root = BitMapBackend::new(out.as_ref(), (1600, 900)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption(format!("{}/{}", long, short), ("sans-serif", 30))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(from..to, min..max)?;
+    chart.configure_mesh().draw()?;
+
+    chart
+        .draw_series(LineSeries::new(rows.iter().map(|(t, v)| (*t, v[1])), &BLUE))?
+        .label("diff")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &BLUE));
+    chart
+        .draw_series(LineSeries::new(
+            rows.iter().map(|(t, v)| (*t, v[3])),
+            &MAGENTA,
+        ))?
+        .label("stdev threshold")
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &MAGENTA));
+    for (value, label) in &[(params.min_diff, "min diff"), (params.max_diff, "max diff")] {
+        chart
+            .draw_series(LineSeries::new(vec![(from, *value), (to, *value)], &BLACK))?
+            .label(*label);
+    }
+
+    let positions: Vec<&Position> = positions
+        .iter()
+        .filter(|p| p.long == long && p.short == short)
+        .collect();
+    chart
+        .draw_series(
+            positions
+                .iter()
+                .filter_map(|p| p.open_time)
+                .map(|t| Circle::new((t, diff_at(t)), 5, GREEN.filled())),
+        )?
+        .label("open")
+        .legend(|(x, y)| Circle::new((x, y), 5, GREEN.filled()));
+    chart
+        .draw_series(
+            positions
+                .iter()
+                .filter_map(|p| p.close_time)
+                .map(|t| Cross::new((t, diff_at(t)), 5, RED.filled())),
+        )?
+        .label("close")
+        .legend(|(x, y)| Cross::new((x, y), 5, RED.filled()));
+
+    chart
+        .configure_series_labels()
+        .background_style(&WHITE.mix(0.8))
+        .border_style(&BLACK)
+        .draw()?;
+    root.present()?;
+    Ok(())
+}
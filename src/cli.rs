@@ -0,0 +1,223 @@
+use crate::{
+    recorder::Recorder,
+    trader::{Coin, Params, Precision, Source, TimeFormat},
+};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use std::{net::SocketAddr, path::PathBuf};
+use structopt::StructOpt;
+
+fn parse_time(s: &str) -> Result<DateTime<Utc>, String> {
+    s.parse::<DateTime<Utc>>()
+        .or_else(|_| {
+            s.parse::<NaiveDate>()
+                .map(|date| DateTime::from_utc(date.and_hms(0, 0, 0), Utc))
+        })
+        .map_err(|err| format!("{}", err))
+}
+
+fn parse_pair(s: &str) -> Result<(Coin, Coin), String> {
+    let mut coins = s.split('/');
+    match (coins.next(), coins.next(), coins.next()) {
+        (Some(long), Some(short), None) => Ok((long.parse()?, short.parse()?)),
+        _ => Err(format!("Expected pair as LONG/SHORT, got {}.", s)),
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "trader")]
+pub struct Opt {
+    /// Start of the time range, as date or RFC 3339 time. From 2021-02-01 by default, or when
+    /// trading, from just long enough ago to warm up the strategy.
+    #[structopt(long, parse(try_from_str = parse_time))]
+    pub from: Option<DateTime<Utc>>,
+    /// End of the time range, open ended by default.
+    #[structopt(long, parse(try_from_str = parse_time))]
+    pub to: Option<DateTime<Utc>>,
+    /// Comma separated list of coins to trade, all coins by default.
+    #[structopt(long, use_delimiter = true, number_of_values = 1)]
+    pub coins: Vec<Coin>,
+    /// Candle interval in seconds.
+    #[structopt(long, default_value = "15")]
+    pub interval: i64,
+    /// TOML file with strategy parameters, costs and optimizer grid.
+    #[structopt(long)]
+    pub config: Option<PathBuf>,
+    /// Directory to write results to.
+    #[structopt(long, default_value = ".")]
+    pub out: PathBuf,
+    /// File to record signals and trades in, as JSON lines or as CBOR if the extension is `.cbor`.
+    #[structopt(long)]
+    pub journal: Option<PathBuf>,
+    /// Comma separated list of coins whose indicators are recorded to the output directory.
+    #[structopt(long, use_delimiter = true, number_of_values = 1)]
+    pub record: Vec<Coin>,
+    /// Pair as LONG/SHORT whose indicators are recorded and, with the `plot` feature, charted.
+    #[structopt(long, number_of_values = 1, parse(try_from_str = parse_pair))]
+    pub record_pair: Vec<(Coin, Coin)>,
+    /// Number type the indicators compute with: f32, f64 or decimal, the configured one by default.
+    #[structopt(long)]
+    pub precision: Option<Precision>,
+    /// Number of threads to update pairs on, one per core by default.
+    #[structopt(long)]
+    pub threads: Option<usize>,
+    /// File to save the strategy state in when paper or live trading, and to restore it from.
+    #[structopt(long)]
+    pub snapshot: Option<PathBuf>,
+    /// File to cache historical candles in.
+    #[structopt(long, default_value = "cache.bin")]
+    pub cache: PathBuf,
+    /// Scenario file to generate synthetic candles from instead of reading the cache.
+    #[structopt(long)]
+    pub synthetic: Option<PathBuf>,
+    /// Address to serve Prometheus metrics on, e.g. 127.0.0.1:9000.
+    #[structopt(long)]
+    pub metrics: Option<SocketAddr>,
+    /// Address to accept control actions on, e.g. 127.0.0.1:9002.
+    #[structopt(long)]
+    pub control: Option<SocketAddr>,
+    /// Address to serve the read-only JSON API on, e.g. 127.0.0.1:9001.
+    #[cfg(feature = "api")]
+    #[structopt(long)]
+    pub api: Option<SocketAddr>,
+    #[structopt(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Runs the strategy on cached historical data.
+    Backtest,
+    /// Runs the strategy on real time data with simulated execution.
+    Paper {
+        /// File to persist positions and wallet in.
+        #[structopt(long, default_value = "paper.cbor")]
+        state: PathBuf,
+    },
+    /// Runs the strategy on real time data.
+    Live,
+    /// Downloads historical data into the cache.
+    FetchData,
+    /// Imports candles from CSV or Parquet files into the cache, aligned to the interval.
+    Import {
+        /// Files to import, each named after its coin like `BTC.csv` unless there is a coin column.
+        files: Vec<PathBuf>,
+        #[structopt(long, default_value = "time")]
+        time_column: String,
+        #[structopt(long, default_value = "close")]
+        close_column: String,
+        #[structopt(long, default_value = "volume")]
+        volume_column: String,
+        /// Column with the coin of each candle, for files with several coins.
+        #[structopt(long)]
+        coin_column: Option<String>,
+        /// Format of the times: rfc3339, unix, unix_ms or a format like "%Y-%m-%d %H:%M:%S".
+        #[structopt(long, default_value = "rfc3339")]
+        time_format: TimeFormat,
+    },
+    /// Reruns the backtest described by a manifest and verifies it produces the same trades.
+    Replay {
+        #[structopt(long, default_value = "manifest.json")]
+        manifest: PathBuf,
+    },
+    /// Prints the report of a saved paper trading state.
+    Report {
+        #[structopt(long, default_value = "paper.cbor")]
+        state: PathBuf,
+    },
+    /// Searches for the best strategy parameters on cached historical data.
+    Optimize {
+        /// Number of random samples from the grid, the full grid is searched by default.
+        #[structopt(long)]
+        samples: Option<usize>,
+        /// Seed of the random search, the configured seed by default.
+        #[structopt(long)]
+        seed: Option<u64>,
+        /// Days of in-sample and out-of-sample windows for walk-forward validation.
+        #[structopt(long, number_of_values = 2)]
+        walk_forward: Option<Vec<i64>>,
+    },
+}
+
+impl Opt {
+    pub fn start(&self, params: &Params) -> DateTime<Utc> {
+        match (self.from, &self.command) {
+            (Some(from), _) => from,
+            (None, Command::Paper { .. }) | (None, Command::Live) => {
+                Utc::now() - Duration::seconds(self.interval) * params.warmup() as i32
+            }
+            (None, _) => Utc.ymd(2021, 2, 1).and_hms(0, 0, 0),
+        }
+    }
+
+    pub fn coins(&self) -> Vec<Coin> {
+        if self.coins.is_empty() {
+            Coin::all()
+        } else {
+            Coin::sorted(&self.coins)
+        }
+    }
+
+    pub fn source(&self) -> Source {
+        match &self.synthetic {
+            Some(path) => Source::Synthetic(path.clone()),
+            None => Source::Cache(self.cache.clone()),
+        }
+    }
+
+    pub fn recorder(&self) -> Recorder {
+        let recorder = self
+            .record
+            .iter()
+            .fold(Recorder::new(&self.out), |recorder, &coin| {
+                recorder.coin(coin)
+            });
+        self.record_pair
+            .iter()
+            .fold(recorder, |recorder, &(long, short)| {
+                recorder.pair(long, short)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let opt = Opt::from_iter(&[
+            "trader",
+            "--from",
+            "2021-03-01",
+            "--coins",
+            "BTC,ETH-PERP",
+            "--record-pair",
+            "BTC/ETH",
+            "--precision",
+            "f64",
+            "optimize",
+            "--walk-forward",
+            "60",
+            "14",
+        ]);
+
+        assert_eq!(
+            opt.start(&Params::default()),
+            Utc.ymd(2021, 3, 1).and_hms(0, 0, 0)
+        );
+        assert_eq!(opt.coins(), vec![Coin::BTC, Coin::ETH]);
+        assert_eq!(opt.recorder().pairs(), &[(Coin::BTC, Coin::ETH)]);
+        assert_eq!(opt.precision, Some(Precision::F64));
+        match opt.command {
+            Command::Optimize { walk_forward, .. } => assert_eq!(walk_forward, Some(vec![60, 14])),
+            _ => panic!("Expected optimize command."),
+        }
+
+        // Only warms up on the candles the strategy needs when trading.
+        let params = Params::default();
+        let opt = Opt::from_iter(&["trader", "--interval", "60", "live"]);
+        let warmup = Utc::now() - opt.start(&params);
+        assert!(warmup >= Duration::minutes(params.warmup() as i64));
+        assert!(warmup < Duration::minutes(params.warmup() as i64 + 1));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::trader::{Coin, Limits};
+use chrono::{DateTime, Utc};
+use hyper::{
+    body::to_bytes,
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    server::conn::AddrStream,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request as HttpRequest, Response, Server, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    convert::Infallible,
+    env::var,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    net::SocketAddr,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    // Stop opening new positions.
+    Pause,
+    Resume,
+    Close { long: Coin, short: Coin },
+    CloseAll,
+    Blacklist { coin: Coin },
+    Whitelist { coin: Coin },
+    BlacklistPair { long: Coin, short: Coin },
+    WhitelistPair { long: Coin, short: Coin },
+    SetLimits { limits: Limits },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub time: DateTime<Utc>,
+    pub source: String,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Audit {
+    #[serde(flatten)]
+    pub request: Request,
+    pub applied: DateTime<Utc>,
+    pub result: String,
+}
+
+// Queues actions for the trader, which applies them before its next tick.
+pub struct Control {
+    sender: UnboundedSender<Request>,
+    receiver: Mutex<UnboundedReceiver<Request>>,
+    audit: Mutex<Option<File>>,
+}
+
+impl Control {
+    pub fn new() -> Self {
+        let (sender, receiver) = unbounded_channel();
+        Control {
+            sender,
+            receiver: Mutex::new(receiver),
+            audit: Mutex::new(None),
+        }
+    }
+
+    // Appends every applied action as JSON line to the given file.
+    pub fn open_audit<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        *self.audit.lock().unwrap() = Some(file);
+        Ok(())
+    }
+
+    pub fn queue(&self, source: &str, action: Action) {
+        self.sender
+            .send(Request {
+                time: Utc::now(),
+                source: source.to_string(),
+                action,
+            })
+            .ok();
+    }
+
+    pub fn pending(&self) -> Vec<Request> {
+        let mut receiver = self.receiver.lock().unwrap();
+        let mut requests = Vec::new();
+        while let Ok(request) = receiver.try_recv() {
+            requests.push(request);
+        }
+        requests
+    }
+
+    pub fn audit(&self, request: Request, result: String) {
+        log::info!(
+            "CONTROL \t{:?} \tFROM {} \t{}",
+            request.action,
+            request.source,
+            result
+        );
+        let audit = Audit {
+            request,
+            applied: Utc::now(),
+            result,
+        };
+        if let Some(file) = self.audit.lock().unwrap().as_mut() {
+            let result = serde_json::to_writer(&mut *file, &audit)
+                .map_err(io::Error::from)
+                .and_then(|_| writeln!(file));
+            if let Err(err) = result {
+                log::error!("Could not write audit log: {}", err);
+            }
+        }
+    }
+}
+
+// Token actions have to be sent with, read from the CONTROL_TOKEN environment variable. Without
+// one, only loopback addresses are served, so only local users can control the trader.
+pub fn token(addr: &SocketAddr) -> Result<Option<String>, String> {
+    match var("CONTROL_TOKEN") {
+        Ok(token) if !token.is_empty() => Ok(Some(token)),
+        _ if addr.ip().is_loopback() => Ok(None),
+        _ => Err(format!(
+            "refusing to accept control actions on {} without CONTROL_TOKEN",
+            addr
+        )),
+    }
+}
+
+fn authorized(req: &HttpRequest<Body>, token: Option<&str>) -> bool {
+    let token = match token {
+        Some(token) => token,
+        None => return true,
+    };
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        == Some(token)
+}
+
+async fn handle(
+    source: SocketAddr,
+    token: Option<Arc<String>>,
+    req: HttpRequest<Body>,
+) -> Result<Response<Body>, Infallible> {
+    if req.method() != Method::POST || req.uri().path() != "/control" {
+        return Ok(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .unwrap());
+    }
+    if !authorized(&req, token.as_deref().map(String::as_str)) {
+        log::warn!("Rejected unauthorized control action from {}.", source);
+        return Ok(Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .body(Body::empty())
+            .unwrap());
+    }
+
+    let body = to_bytes(req.into_body()).await.unwrap_or_default();
+    let response = match serde_json::from_slice::<Action>(&body) {
+        Ok(action) => {
+            crate::CONTROL.queue(&source.to_string(), action);
+            Response::builder()
+                .status(StatusCode::ACCEPTED)
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"queued":true}"#))
+        }
+        Err(err) => Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(Body::from(err.to_string())),
+    };
+    Ok(response.unwrap())
+}
+
+pub async fn serve(addr: SocketAddr, token: Option<String>) {
+    let server = match Server::try_bind(&addr) {
+        Ok(server) => server,
+        Err(err) => {
+            log::error!("Could not accept control actions on {}: {}", addr, err);
+            return;
+        }
+    };
+    log::info!("Accepting control actions on http://{}/control.", addr);
+    let token = token.map(Arc::new);
+    let service = make_service_fn(|conn: &AddrStream| {
+        let source = conn.remote_addr();
+        let token = token.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(source, token.clone(), req))) }
+    });
+    if let Err(err) = server.serve(service).await {
+        log::error!("Control server failed: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn parse() {
+        let action: Action =
+            serde_json::from_str(r#"{"action":"close","long":"BTC","short":"ETH"}"#).unwrap();
+        assert_eq!(
+            action,
+            Action::Close {
+                long: Coin::BTC,
+                short: Coin::ETH
+            }
+        );
+
+        let action: Action =
+            serde_json::from_str(r#"{"action":"set_limits","limits":{"max_loss":"10"}}"#).unwrap();
+        assert_eq!(
+            action,
+            Action::SetLimits {
+                limits: Limits {
+                    max_positions: None,
+                    max_loss: Some(Decimal::new(10, 0)),
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn authorize() {
+        let req = |auth: Option<&str>| {
+            let mut req = HttpRequest::post("/control");
+            if let Some(auth) = auth {
+                req = req.header(AUTHORIZATION, auth);
+            }
+            req.body(Body::empty()).unwrap()
+        };
+        assert!(authorized(&req(None), None));
+        assert!(!authorized(&req(None), Some("secret")));
+        assert!(!authorized(&req(Some("Bearer wrong")), Some("secret")));
+        assert!(authorized(&req(Some("Bearer secret")), Some("secret")));
+
+        let local: SocketAddr = "127.0.0.1:9002".parse().unwrap();
+        let public: SocketAddr = "0.0.0.0:9002".parse().unwrap();
+        if var("CONTROL_TOKEN").is_err() {
+            assert_eq!(token(&local), Ok(None));
+            assert!(token(&public).is_err());
+        }
+    }
+
+    #[test]
+    fn pending() {
+        let control = Control::new();
+        control.queue("test", Action::Pause);
+        control.queue("test", Action::CloseAll);
+
+        let actions: Vec<Action> = control.pending().into_iter().map(|r| r.action).collect();
+        assert_eq!(actions, vec![Action::Pause, Action::CloseAll]);
+        assert!(control.pending().is_empty());
+    }
+}
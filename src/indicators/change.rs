@@ -1,23 +1,49 @@
+use super::{Indicator, Real};
 use crate::Number;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone)]
-pub struct Change {
-    last: Option<Number>,
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Change<T = Number> {
+    last: Option<T>,
+    output: Option<T>,
 }
 
-impl Change {
+impl<T: Real> Change<T> {
     pub fn new() -> Self {
-        Self { last: None }
+        Self {
+            last: None,
+            output: None,
+        }
     }
 
-    pub fn run(&mut self, input: Number) -> Number {
-        let output = if let Some(last) = self.last {
-            (input - last) / last
-        } else {
-            0.0
-        };
+    pub fn run(&mut self, input: T) -> T {
+        self.output = self.last.map(|last| {
+            if last.is_zero() {
+                T::zero()
+            } else {
+                (input - last) / last
+            }
+        });
         self.last = Some(input);
-        output
+        self.output.unwrap_or_else(T::zero)
+    }
+}
+
+impl<T: Real> Indicator for Change<T> {
+    type Input = T;
+    type Output = T;
+
+    fn update(&mut self, input: T) {
+        self.run(input);
+    }
+
+    fn value(&self) -> Option<T> {
+        self.output
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+        self.output = None;
     }
 }
 
@@ -1,31 +1,56 @@
-use super::{Cov, Stdev};
+use super::{CoMoment, Indicator, Real, Window};
 use crate::Number;
+use serde::{Deserialize, Serialize};
 
-pub struct Corr {
-    cov: Cov,
-    stdev_x: Stdev,
-    stdev_y: Stdev,
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Corr<T: Real = Number> {
+    x: Window<T>,
+    y: Window<T>,
+    co: CoMoment<T::Acc>,
 }
 
-impl Corr {
+impl<T: Real> Corr<T> {
     pub fn new(period: usize) -> Self {
         assert!(period >= 1);
 
         Self {
-            cov: Cov::new(period),
-            stdev_x: Stdev::new(period),
-            stdev_y: Stdev::new(period),
+            x: Window::new(period),
+            y: Window::new(period),
+            co: CoMoment::new(),
         }
     }
 
-    pub fn run(&mut self, x: Number, y: Number) -> Number {
-        let d = self.stdev_x.run(x) * self.stdev_y.run(y);
-        if d == 0.0 {
-            0.0
+    // Always within [-1, 1], zero if either input is constant.
+    pub fn run(&mut self, x: T, y: T) -> T {
+        self.x.push(x);
+        self.y.push(y);
+        self.co.update(&self.x, &self.y);
+        T::from_acc(self.co.corr(&self.x, &self.y))
+    }
+}
+
+impl<T: Real> Indicator for Corr<T> {
+    type Input = (T, T);
+    type Output = T;
+
+    fn update(&mut self, (x, y): (T, T)) {
+        self.run(x, y);
+    }
+
+    fn value(&self) -> Option<T> {
+        if self.x.is_full() {
+            Some(T::from_acc(self.co.corr(&self.x, &self.y)))
         } else {
-            self.cov.run(x, y) / d
+            None
         }
     }
+
+    fn reset(&mut self) {
+        self.x.reset();
+        self.y.reset();
+        self.co.reset();
+    }
 }
 
 #[cfg(test)]
@@ -40,6 +65,11 @@ mod tests {
         for i in 0..100 {
             assert!(corr.run(i as f32, i as f32) >= 0.0);
         }
+        assert!(corr.value().unwrap() > 0.0);
+
+        corr.reset();
+        corr.update((1.0, 1.0));
+        assert!(!corr.is_ready());
     }
 
     #[test]
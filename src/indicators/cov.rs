@@ -1,25 +1,65 @@
-use super::Ma;
-
+use super::{CoMoment, Indicator, Real, Window};
 use crate::Number;
+use serde::{Deserialize, Serialize};
 
-pub struct Cov {
-    x_avg: Ma,
-    y_avg: Ma,
-    cov: Ma,
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Cov<T: Real = Number> {
+    x: Window<T>,
+    y: Window<T>,
+    co: CoMoment<T::Acc>,
+    ddof: usize,
 }
 
-impl Cov {
+impl<T: Real> Cov<T> {
+    // Population covariance of the window.
     pub fn new(period: usize) -> Self {
+        debug_assert!(period >= 1);
+
+        Self {
+            x: Window::new(period),
+            y: Window::new(period),
+            co: CoMoment::new(),
+            ddof: 0,
+        }
+    }
+
+    // Sample covariance of the window.
+    pub fn sample(period: usize) -> Self {
         Self {
-            x_avg: Ma::new(period),
-            y_avg: Ma::new(period),
-            cov: Ma::new(period),
+            ddof: 1,
+            ..Self::new(period)
+        }
+    }
+
+    pub fn run(&mut self, x: T, y: T) -> T {
+        self.x.push(x);
+        self.y.push(y);
+        self.co.update(&self.x, &self.y);
+        T::from_acc(self.co.cov(&self.x, self.ddof))
+    }
+}
+
+impl<T: Real> Indicator for Cov<T> {
+    type Input = (T, T);
+    type Output = T;
+
+    fn update(&mut self, (x, y): (T, T)) {
+        self.run(x, y);
+    }
+
+    fn value(&self) -> Option<T> {
+        if self.x.is_full() {
+            Some(T::from_acc(self.co.cov(&self.x, self.ddof)))
+        } else {
+            None
         }
     }
 
-    pub fn run(&mut self, x: Number, y: Number) -> Number {
-        self.cov
-            .run((x - self.x_avg.run(x)) * (y - self.y_avg.run(y)))
+    fn reset(&mut self) {
+        self.x.reset();
+        self.y.reset();
+        self.co.reset();
     }
 }
 
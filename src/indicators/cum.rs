@@ -1,21 +1,46 @@
+use super::{Indicator, Real};
 use crate::Number;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone)]
-pub struct Cum {
-    sum: Number,
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Cum<T = Number> {
+    sum: T,
+    ready: bool,
 }
 
-impl Cum {
+impl<T: Real> Cum<T> {
     pub fn new() -> Self {
-        Self { sum: 0.0 }
+        Self {
+            sum: T::zero(),
+            ready: false,
+        }
     }
 
-    pub fn run(&mut self, input: Number) -> Number {
+    pub fn run(&mut self, input: T) -> T {
         self.sum += input;
+        self.ready = true;
         self.sum
     }
 }
 
+impl<T: Real> Indicator for Cum<T> {
+    type Input = T;
+    type Output = T;
+
+    fn update(&mut self, input: T) {
+        self.run(input);
+    }
+
+    fn value(&self) -> Option<T> {
+        Some(self.sum).filter(|_| self.ready)
+    }
+
+    fn reset(&mut self) {
+        self.sum = T::zero();
+        self.ready = false;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,82 @@
+use super::{Indicator, Real};
+use crate::Number;
+use serde::{Deserialize, Serialize};
+
+// Exponential moving average, seeded with the first input.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Ema<T = Number> {
+    alpha: T,
+    period: usize,
+    count: usize,
+    output: Option<T>,
+}
+
+impl<T: Real> Ema<T> {
+    pub fn new(period: usize) -> Self {
+        Self::with_alpha(
+            T::from_usize(2) / (T::from_usize(period) + T::one()),
+            period,
+        )
+    }
+
+    // Warms up for `period` inputs, but weights new inputs by the given factor.
+    pub fn with_alpha(alpha: T, period: usize) -> Self {
+        debug_assert!(period >= 1);
+        debug_assert!(T::zero() < alpha && alpha <= T::one());
+
+        Self {
+            alpha,
+            period,
+            count: 0,
+            output: None,
+        }
+    }
+
+    pub fn run(&mut self, input: T) -> T {
+        let output = match self.output {
+            Some(last) => last + self.alpha * (input - last),
+            None => input,
+        };
+        self.count += 1;
+        self.output = Some(output);
+        output
+    }
+}
+
+impl<T: Real> Indicator for Ema<T> {
+    type Input = T;
+    type Output = T;
+
+    fn update(&mut self, input: T) {
+        self.run(input);
+    }
+
+    fn value(&self) -> Option<T> {
+        self.output.filter(|_| self.count >= self.period)
+    }
+
+    fn reset(&mut self) {
+        self.count = 0;
+        self.output = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic() {
+        let mut ema = Ema::new(3);
+
+        assert_eq!(ema.run(2.0), 2.0);
+        assert_eq!(ema.run(4.0), 3.0);
+        assert_eq!(ema.value(), None);
+        assert_eq!(ema.run(6.0), 4.5);
+        assert_eq!(ema.value(), Some(4.5));
+
+        ema.reset();
+        assert_eq!(ema.value(), None);
+        assert_eq!(ema.run(8.0), 8.0);
+    }
+}
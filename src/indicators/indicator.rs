@@ -0,0 +1,69 @@
+use super::{Chain, Diff, Fork, Lag, Map, Real, Zip};
+
+pub trait Indicator {
+    type Input;
+    type Output: Copy;
+
+    fn update(&mut self, input: Self::Input);
+
+    // None until enough inputs were seen to fill the window.
+    fn value(&self) -> Option<Self::Output>;
+
+    fn is_ready(&self) -> bool {
+        self.value().is_some()
+    }
+
+    // Forgets all inputs, as if newly created.
+    fn reset(&mut self);
+
+    fn map<F: Fn(Self::Output) -> Self::Output>(self, f: F) -> Map<Self, F>
+    where
+        Self: Sized,
+    {
+        Map::new(self, f)
+    }
+
+    fn chain<B: Indicator<Input = Self::Output>>(self, next: B) -> Chain<Self, B>
+    where
+        Self: Sized,
+    {
+        Chain::new(self, next)
+    }
+
+    fn zip<B: Indicator, F: Fn(Self::Output, B::Output) -> Self::Output>(
+        self,
+        other: B,
+        f: F,
+    ) -> Zip<Self, B, F>
+    where
+        Self: Sized,
+    {
+        Zip::new(self, other, f)
+    }
+
+    fn fork<B: Indicator<Input = Self::Input>, F: Fn(Self::Output, B::Output) -> Self::Output>(
+        self,
+        other: B,
+        f: F,
+    ) -> Fork<Self, B, F>
+    where
+        Self: Sized,
+    {
+        Fork::new(self, other, f)
+    }
+
+    fn lag(self, lag: usize) -> Chain<Self, Lag<Self::Output>>
+    where
+        Self: Sized,
+    {
+        Chain::new(self, Lag::new(lag))
+    }
+
+    fn diff(self) -> Chain<Self, Diff<Self::Output>>
+    where
+        Self: Sized,
+        Self::Output: Real,
+    {
+        Chain::new(self, Diff::new())
+    }
+}
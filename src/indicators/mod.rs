@@ -4,15 +4,33 @@ mod change;
 mod corr;
 mod cov;
 mod cum;
+mod ema;
+mod indicator;
+mod norm;
+mod pipeline;
+mod real;
+mod ring;
+mod rolling;
 mod sma;
+mod snapshot;
 mod stdev;
-//mod norm;
+mod wilder;
+mod wma;
 
 // Use SMA to compute moving averages.
 pub use change::Change;
 pub use corr::Corr;
 pub use cov::Cov;
 pub use cum::Cum;
+pub use ema::Ema;
+pub use indicator::Indicator;
+pub use norm::Norm;
+pub use pipeline::{Chain, Diff, Fork, Lag, Last, Map, Zip};
+pub use real::Real;
+pub use ring::Ring;
+pub use rolling::{CoMoment, KahanSum, Window};
 pub use sma::Sma as Ma;
+pub use snapshot::Snapshot;
 pub use stdev::Stdev;
-//pub use norm::Norm;
+pub use wilder::Wilder;
+pub use wma::Wma;
@@ -1,13 +1,16 @@
-use super::{Ema, Stdev};
+use super::{Ema, Indicator, Real, Stdev};
 use crate::Number;
+use serde::{Deserialize, Serialize};
 
-pub struct Norm {
-    mean: Ema,
-    stdev: Stdev,
-    output: Option<Number>,
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Norm<T: Real = Number> {
+    mean: Ema<T>,
+    stdev: Stdev<T>,
+    output: Option<T>,
 }
 
-impl Norm {
+impl<T: Real> Norm<T> {
     pub fn new(period: usize) -> Self {
         Self {
             mean: Ema::new(period),
@@ -16,32 +19,42 @@ impl Norm {
         }
     }
 
-    pub fn run(&mut self, input: Number) {
+    pub fn run(&mut self, input: T) -> T {
         self.mean.run(input);
         self.stdev.run(input);
-        if let (Some(stdev), Some(mean)) = (self.stdev.get(), self.mean.get()) {
-            self.output = Some((input - mean) / stdev);
-        } else {
-            self.output = None;
-        }
+        self.output = match (self.stdev.value(), self.mean.value()) {
+            (Some(stdev), Some(mean)) if stdev > T::zero() => Some((input - mean) / stdev),
+            (Some(_), Some(_)) => Some(T::zero()),
+            _ => None,
+        };
+        self.output.unwrap_or_else(T::zero)
     }
 
-    pub fn reset(&mut self) {
-        self.mean.reset();
-        self.stdev.reset();
-        self.output = None;
+    pub fn get_mean(&self) -> Option<T> {
+        self.mean.value()
     }
 
-    pub fn get(&self) -> Option<Number> {
-        self.output
+    pub fn get_stdev(&self) -> Option<T> {
+        self.stdev.value()
+    }
+}
+
+impl<T: Real> Indicator for Norm<T> {
+    type Input = T;
+    type Output = T;
+
+    fn update(&mut self, input: T) {
+        self.run(input);
     }
 
-    pub fn get_mean(&self) -> Option<Number> {
-        self.mean.get()
+    fn value(&self) -> Option<T> {
+        self.output
     }
 
-    pub fn get_stdev(&self) -> Option<Number> {
-        self.stdev.get()
+    fn reset(&mut self) {
+        self.mean.reset();
+        self.stdev.reset();
+        self.output = None;
     }
 }
 
@@ -51,6 +64,17 @@ mod tests {
 
     #[test]
     fn basic() {
+        let mut norm = Norm::new(3);
+
+        norm.run(1.0);
+        norm.run(1.0);
+        assert_eq!(norm.value(), None);
+        norm.run(1.0);
+        assert_eq!(norm.value(), Some(0.0));
+        norm.run(10.0);
+        assert!(norm.value().unwrap() > 0.0);
 
+        norm.reset();
+        assert_eq!(norm.value(), None);
     }
 }
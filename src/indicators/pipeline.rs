@@ -0,0 +1,326 @@
+use super::{Indicator, Real, Ring};
+use crate::Number;
+use serde::{Deserialize, Serialize};
+
+// Passes its input through, to start a pipeline.
+#[derive(Copy, Clone, Default, Serialize, Deserialize)]
+pub struct Last<T = Number> {
+    output: Option<T>,
+}
+
+impl<T: Copy> Last<T> {
+    pub fn new() -> Self {
+        Self { output: None }
+    }
+}
+
+impl<T: Copy> Indicator for Last<T> {
+    type Input = T;
+    type Output = T;
+
+    fn update(&mut self, input: T) {
+        self.output = Some(input);
+    }
+
+    fn value(&self) -> Option<T> {
+        self.output
+    }
+
+    fn reset(&mut self) {
+        self.output = None;
+    }
+}
+
+// Applies a function to the value of an indicator.
+#[derive(Copy, Clone)]
+pub struct Map<I, F> {
+    inner: I,
+    f: F,
+}
+
+impl<I, F> Map<I, F> {
+    pub fn new(inner: I, f: F) -> Self {
+        Self { inner, f }
+    }
+
+    pub fn inner(&self) -> &I {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+}
+
+impl<I: Indicator, F: Fn(I::Output) -> I::Output> Indicator for Map<I, F> {
+    type Input = I::Input;
+    type Output = I::Output;
+
+    fn update(&mut self, input: I::Input) {
+        self.inner.update(input);
+    }
+
+    fn value(&self) -> Option<I::Output> {
+        self.inner.value().map(&self.f)
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+// Feeds the values of the first indicator into the second, once the first is ready.
+#[derive(Copy, Clone)]
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> Chain<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+
+    pub fn first(&self) -> &A {
+        &self.first
+    }
+
+    pub fn second(&self) -> &B {
+        &self.second
+    }
+
+    pub fn parts_mut(&mut self) -> (&mut A, &mut B) {
+        (&mut self.first, &mut self.second)
+    }
+}
+
+impl<A: Indicator, B: Indicator<Input = A::Output>> Indicator for Chain<A, B> {
+    type Input = A::Input;
+    type Output = B::Output;
+
+    fn update(&mut self, input: A::Input) {
+        self.first.update(input);
+        if let Some(value) = self.first.value() {
+            self.second.update(value);
+        }
+    }
+
+    fn value(&self) -> Option<B::Output> {
+        self.second.value()
+    }
+
+    fn reset(&mut self) {
+        self.first.reset();
+        self.second.reset();
+    }
+}
+
+// Combines the values of two indicators on two separate streams.
+#[derive(Copy, Clone)]
+pub struct Zip<A, B, F> {
+    a: A,
+    b: B,
+    f: F,
+}
+
+impl<A, B, F> Zip<A, B, F> {
+    pub fn new(a: A, b: B, f: F) -> Self {
+        Self { a, b, f }
+    }
+
+    pub fn parts(&self) -> (&A, &B) {
+        (&self.a, &self.b)
+    }
+
+    pub fn parts_mut(&mut self) -> (&mut A, &mut B) {
+        (&mut self.a, &mut self.b)
+    }
+}
+
+impl<A, B, F> Indicator for Zip<A, B, F>
+where
+    A: Indicator,
+    B: Indicator,
+    F: Fn(A::Output, B::Output) -> A::Output,
+{
+    type Input = (A::Input, B::Input);
+    type Output = A::Output;
+
+    fn update(&mut self, (a, b): (A::Input, B::Input)) {
+        self.a.update(a);
+        self.b.update(b);
+    }
+
+    fn value(&self) -> Option<A::Output> {
+        Some((self.f)(self.a.value()?, self.b.value()?))
+    }
+
+    fn reset(&mut self) {
+        self.a.reset();
+        self.b.reset();
+    }
+}
+
+// Same as `Zip`, but both indicators run on the same stream.
+#[derive(Copy, Clone)]
+pub struct Fork<A, B, F> {
+    a: A,
+    b: B,
+    f: F,
+}
+
+impl<A, B, F> Fork<A, B, F> {
+    pub fn new(a: A, b: B, f: F) -> Self {
+        Self { a, b, f }
+    }
+
+    pub fn parts(&self) -> (&A, &B) {
+        (&self.a, &self.b)
+    }
+
+    pub fn parts_mut(&mut self) -> (&mut A, &mut B) {
+        (&mut self.a, &mut self.b)
+    }
+}
+
+impl<A, B, F> Indicator for Fork<A, B, F>
+where
+    A: Indicator,
+    B: Indicator<Input = A::Input>,
+    A::Input: Copy,
+    F: Fn(A::Output, B::Output) -> A::Output,
+{
+    type Input = A::Input;
+    type Output = A::Output;
+
+    fn update(&mut self, input: A::Input) {
+        self.a.update(input);
+        self.b.update(input);
+    }
+
+    fn value(&self) -> Option<A::Output> {
+        Some((self.f)(self.a.value()?, self.b.value()?))
+    }
+
+    fn reset(&mut self) {
+        self.a.reset();
+        self.b.reset();
+    }
+}
+
+// The input from the given number of updates ago.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Lag<T = Number> {
+    data: Ring<T>,
+}
+
+impl<T: Copy> Lag<T> {
+    pub fn new(lag: usize) -> Self {
+        Self {
+            data: Ring::new(lag + 1),
+        }
+    }
+}
+
+impl<T: Copy> Indicator for Lag<T> {
+    type Input = T;
+    type Output = T;
+
+    fn update(&mut self, input: T) {
+        self.data.push(input);
+    }
+
+    fn value(&self) -> Option<T> {
+        if self.data.is_full() {
+            self.data.front()
+        } else {
+            None
+        }
+    }
+
+    fn reset(&mut self) {
+        self.data.clear();
+    }
+}
+
+// Difference to the previous input.
+#[derive(Copy, Clone, Default, Serialize, Deserialize)]
+pub struct Diff<T = Number> {
+    last: Option<T>,
+    output: Option<T>,
+}
+
+impl<T> Diff<T> {
+    pub fn new() -> Self {
+        Self {
+            last: None,
+            output: None,
+        }
+    }
+}
+
+impl<T: Real> Indicator for Diff<T> {
+    type Input = T;
+    type Output = T;
+
+    fn update(&mut self, input: T) {
+        self.output = self.last.map(|last| input - last);
+        self.last = Some(input);
+    }
+
+    fn value(&self) -> Option<T> {
+        self.output
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+        self.output = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::{Change, Cum, Ma};
+
+    #[test]
+    fn chain() {
+        let mut cum = Change::new().chain(Cum::new()).map(|x| x * 100.0);
+
+        cum.update(10.0);
+        assert_eq!(cum.value(), None);
+        cum.update(20.0);
+        assert_eq!(cum.value(), Some(100.0));
+        cum.update(10.0);
+        assert_eq!(cum.value(), Some(50.0));
+
+        cum.reset();
+        assert!(!cum.is_ready());
+    }
+
+    #[test]
+    fn zip() {
+        let mut spread = Ma::new(2).zip(Ma::new(2), |x, y| x - y);
+        spread.update((1.0, 2.0));
+        assert_eq!(spread.value(), None);
+        spread.update((3.0, 2.0));
+        assert_eq!(spread.value(), Some(0.0));
+
+        let mut mov = Last::new().fork(Ma::new(2), |x, mean| x - mean);
+        mov.update(2.0);
+        mov.update(4.0);
+        assert_eq!(mov.value(), Some(1.0));
+    }
+
+    #[test]
+    fn lag() {
+        let mut lag = Last::new().lag(2);
+        let mut diff = Last::new().diff();
+        for i in 0..5 {
+            lag.update(i as Number);
+            diff.update((i * i) as Number);
+        }
+        assert_eq!(lag.value(), Some(2.0));
+        assert_eq!(diff.value(), Some(7.0));
+    }
+}
@@ -0,0 +1,184 @@
+use rust_decimal::{prelude::*, MathematicalOps};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fmt::Debug,
+    ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign},
+};
+
+// Numeric type indicators compute with.
+pub trait Real:
+    Copy
+    + Debug
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + Send
+    + Sync
+    + Serialize
+    + DeserializeOwned
+    + 'static
+{
+    // Type sums and moments are accumulated in, at least as precise as the type itself.
+    type Acc: Real;
+
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn from_usize(n: usize) -> Self;
+    fn from_f64(x: f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn from_decimal(x: Decimal) -> Self;
+    fn to_decimal(self) -> Option<Decimal>;
+    fn to_acc(self) -> Self::Acc;
+    fn from_acc(acc: Self::Acc) -> Self;
+    fn sqrt(self) -> Self;
+    fn abs(self) -> Self;
+
+    fn max(self, other: Self) -> Self {
+        if self < other {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn min(self, other: Self) -> Self {
+        if other < self {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn is_zero(self) -> bool {
+        self == Self::zero()
+    }
+}
+
+macro_rules! float {
+    ($t:ty, $acc:ty) => {
+        impl Real for $t {
+            type Acc = $acc;
+
+            fn zero() -> Self {
+                0.0
+            }
+
+            fn one() -> Self {
+                1.0
+            }
+
+            fn from_usize(n: usize) -> Self {
+                n as $t
+            }
+
+            fn from_f64(x: f64) -> Self {
+                x as $t
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn from_decimal(x: Decimal) -> Self {
+                ToPrimitive::to_f64(&x).unwrap() as $t
+            }
+
+            fn to_decimal(self) -> Option<Decimal> {
+                <Decimal as FromPrimitive>::from_f64(self as f64)
+            }
+
+            fn to_acc(self) -> $acc {
+                self as $acc
+            }
+
+            fn from_acc(acc: $acc) -> Self {
+                acc as $t
+            }
+
+            fn sqrt(self) -> Self {
+                <$t>::sqrt(self)
+            }
+
+            fn abs(self) -> Self {
+                <$t>::abs(self)
+            }
+        }
+    };
+}
+
+float!(f32, f64);
+float!(f64, f64);
+
+impl Real for Decimal {
+    type Acc = Decimal;
+
+    fn zero() -> Self {
+        Decimal::ZERO
+    }
+
+    fn one() -> Self {
+        Decimal::ONE
+    }
+
+    fn from_usize(n: usize) -> Self {
+        Decimal::from(n)
+    }
+
+    fn from_f64(x: f64) -> Self {
+        <Decimal as FromPrimitive>::from_f64(x).unwrap()
+    }
+
+    fn to_f64(self) -> f64 {
+        ToPrimitive::to_f64(&self).unwrap()
+    }
+
+    fn from_decimal(x: Decimal) -> Self {
+        x
+    }
+
+    fn to_decimal(self) -> Option<Decimal> {
+        Some(self)
+    }
+
+    fn to_acc(self) -> Decimal {
+        self
+    }
+
+    fn from_acc(acc: Decimal) -> Self {
+        acc
+    }
+
+    fn sqrt(self) -> Self {
+        MathematicalOps::sqrt(&self).unwrap_or(Decimal::ZERO)
+    }
+
+    fn abs(self) -> Self {
+        Decimal::abs(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hypot<T: Real>(x: T, y: T) -> T {
+        (x * x + y * y).sqrt()
+    }
+
+    #[test]
+    fn real() {
+        assert_eq!(hypot(3.0f32, 4.0), 5.0);
+        assert_eq!(hypot(3.0f64, 4.0), 5.0);
+        assert_eq!(
+            hypot(Decimal::new(3, 0), Decimal::new(4, 0)),
+            Decimal::new(5, 0)
+        );
+        assert_eq!(<f32 as Real>::from_decimal(Decimal::new(25, 1)), 2.5);
+        assert_eq!(Real::max(-1.0f32, 2.0), 2.0);
+    }
+}
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+// Fixed capacity buffer, allocated once, that overwrites its oldest element when full.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Ring<T> {
+    data: Vec<T>,
+    head: usize,
+    capacity: usize,
+}
+
+impl<T: Copy> Ring<T> {
+    pub fn new(capacity: usize) -> Self {
+        debug_assert!(capacity >= 1);
+
+        Self {
+            data: Vec::with_capacity(capacity),
+            head: 0,
+            capacity,
+        }
+    }
+
+    // Returns the element that was evicted to make room.
+    pub fn push(&mut self, input: T) -> Option<T> {
+        if self.data.len() < self.capacity {
+            self.data.push(input);
+            None
+        } else {
+            let evicted = std::mem::replace(&mut self.data[self.head], input);
+            self.head = (self.head + 1) % self.capacity;
+            Some(evicted)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.data.len() == self.capacity
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn front(&self) -> Option<T> {
+        self.get(0)
+    }
+
+    pub fn back(&self) -> Option<T> {
+        self.len().checked_sub(1).and_then(|i| self.get(i))
+    }
+
+    // Oldest element first.
+    pub fn get(&self, i: usize) -> Option<T> {
+        if i < self.data.len() {
+            Some(self.data[(self.head + i) % self.data.len()])
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        let (newer, older) = self.data.split_at(self.head);
+        older.iter().chain(newer).copied()
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.head = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring() {
+        let mut ring = Ring::new(3);
+        assert_eq!(ring.push(1), None);
+        assert_eq!(ring.push(2), None);
+        assert!(!ring.is_full());
+        assert_eq!(ring.push(3), None);
+        assert_eq!(ring.push(4), Some(1));
+        assert_eq!(ring.push(5), Some(2));
+        assert_eq!(ring.iter().collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert_eq!((ring.front(), ring.back()), (Some(3), Some(5)));
+        assert_eq!(ring.data.capacity(), 3);
+
+        ring.clear();
+        assert_eq!(ring.front(), None);
+    }
+}
@@ -0,0 +1,279 @@
+use super::{Real, Ring};
+use crate::Number;
+use serde::{Deserialize, Serialize};
+
+// Compensated (Neumaier) summation, so long running sums don't drift.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct KahanSum<T = f64> {
+    sum: T,
+    compensation: T,
+}
+
+impl<T: Real> KahanSum<T> {
+    pub fn new() -> Self {
+        Self {
+            sum: T::zero(),
+            compensation: T::zero(),
+        }
+    }
+
+    pub fn add(&mut self, input: T) {
+        let sum = self.sum + input;
+        if self.sum.abs() >= input.abs() {
+            self.compensation += (self.sum - sum) + input;
+        } else {
+            self.compensation += (input - sum) + self.sum;
+        }
+        self.sum = sum;
+    }
+
+    pub fn get(&self) -> T {
+        self.sum + self.compensation
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+// Rolling window over one series with its mean and second moment, updated with Welford's method
+// as inputs enter and leave. Indicators on the same series can share one window.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Window<T: Real = Number> {
+    ring: Ring<T>,
+    mean: T::Acc,
+    m2: T::Acc,
+    step: Step<T::Acc>,
+}
+
+// What the last update changed, for co-moments with windows of other series.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Step<A> {
+    input: A,
+    evicted: Option<A>,
+    // Mean before the update, after evicting and after adding the input.
+    mean_before: A,
+    mean_evicted: A,
+    mean: A,
+}
+
+impl<T: Real> Window<T> {
+    pub fn new(period: usize) -> Self {
+        Self {
+            ring: Ring::new(period),
+            mean: T::Acc::zero(),
+            m2: T::Acc::zero(),
+            step: Step {
+                input: T::Acc::zero(),
+                evicted: None,
+                mean_before: T::Acc::zero(),
+                mean_evicted: T::Acc::zero(),
+                mean: T::Acc::zero(),
+            },
+        }
+    }
+
+    pub fn push(&mut self, input: T) {
+        let x = input.to_acc();
+        let mean_before = self.mean;
+        let evicted = self.ring.push(input).map(T::to_acc);
+        if let Some(old) = evicted {
+            let n = self.ring.len() - 1;
+            if n == 0 {
+                self.mean = T::Acc::zero();
+                self.m2 = T::Acc::zero();
+            } else {
+                let mean = self.mean - (old - self.mean) / T::Acc::from_usize(n);
+                self.m2 = (self.m2 - (old - mean) * (old - self.mean)).max(T::Acc::zero());
+                self.mean = mean;
+            }
+        }
+        let mean_evicted = self.mean;
+        let dx = x - self.mean;
+        self.mean += dx / T::Acc::from_usize(self.ring.len());
+        self.m2 += dx * (x - self.mean);
+        self.step = Step {
+            input: x,
+            evicted,
+            mean_before,
+            mean_evicted,
+            mean: self.mean,
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.ring.is_full()
+    }
+
+    pub fn period(&self) -> usize {
+        self.ring.capacity()
+    }
+
+    pub fn ring(&self) -> &Ring<T> {
+        &self.ring
+    }
+
+    pub fn mean(&self) -> T::Acc {
+        self.mean
+    }
+
+    // Population variance for `ddof` 0, sample variance for 1.
+    pub fn var(&self, ddof: usize) -> T::Acc {
+        normalize(self.m2, self.len(), ddof)
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new(self.period());
+    }
+}
+
+// Co-moment of two windows of the same period that are pushed in lockstep, so pairs of series
+// don't need their own copies of the inputs.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct CoMoment<A> {
+    c: A,
+}
+
+impl<A: Real> CoMoment<A> {
+    pub fn new() -> Self {
+        Self { c: A::zero() }
+    }
+
+    // Call after both windows were pushed.
+    pub fn update<T: Real<Acc = A>>(&mut self, x: &Window<T>, y: &Window<T>) {
+        debug_assert_eq!(x.len(), y.len());
+
+        let (x, y) = (&x.step, &y.step);
+        if let (Some(old_x), Some(old_y)) = (x.evicted, y.evicted) {
+            self.c -= (old_x - x.mean_evicted) * (old_y - y.mean_before);
+        }
+        self.c += (x.input - x.mean_evicted) * (y.input - y.mean);
+    }
+
+    pub fn cov<T: Real<Acc = A>>(&self, x: &Window<T>, ddof: usize) -> A {
+        normalize(self.c, x.len(), ddof)
+    }
+
+    // Always within [-1, 1], zero if either series is constant.
+    pub fn corr<T: Real<Acc = A>>(&self, x: &Window<T>, y: &Window<T>) -> A {
+        let d = (x.m2 * y.m2).sqrt();
+        if d.is_zero() {
+            A::zero()
+        } else {
+            (self.c / d).max(-A::one()).min(A::one())
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+fn normalize<A: Real>(m: A, n: usize, ddof: usize) -> A {
+    if n > ddof {
+        m / A::from_usize(n - ddof)
+    } else {
+        A::zero()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        indicators::{Corr, Cov, Indicator, Ma, Stdev},
+        Number,
+    };
+    use proptest::prelude::*;
+
+    fn window(data: &[(Number, Number)], i: usize, period: usize) -> &[(Number, Number)] {
+        &data[(i + 1).saturating_sub(period)..=i]
+    }
+
+    // Mean and standard deviation of x, standard deviation of y, covariance and correlation.
+    fn naive(window: &[(Number, Number)], ddof: usize) -> (f64, f64, f64, f64, f64) {
+        let n = window.len() as f64;
+        let mean_x = window.iter().map(|&(x, _)| x as f64).sum::<f64>() / n;
+        let mean_y = window.iter().map(|&(_, y)| y as f64).sum::<f64>() / n;
+        let m = |f: &dyn Fn(f64, f64) -> f64| {
+            window
+                .iter()
+                .map(|&(x, y)| f(x as f64 - mean_x, y as f64 - mean_y))
+                .sum::<f64>()
+        };
+        let (m2_x, m2_y, c) = (m(&|x, _| x * x), m(&|_, y| y * y), m(&|x, y| x * y));
+        let d = (m2_x * m2_y).sqrt();
+        let corr = if d == 0.0 { 0.0 } else { c / d };
+        let norm = if window.len() > ddof {
+            n - ddof as f64
+        } else {
+            1.0
+        };
+        (
+            mean_x,
+            (m2_x / norm).sqrt(),
+            (m2_y / norm).sqrt(),
+            c / norm,
+            corr,
+        )
+    }
+
+    fn close(a: Number, b: f64, scale: f64) -> bool {
+        (a as f64 - b).abs() <= 1e-5 * scale.max(1.0)
+    }
+
+    proptest! {
+        #[test]
+        fn rolling(
+            data in prop::collection::vec((-1e3f32..1e3, -1e3f32..1e3), 1..300),
+            period in 1usize..50,
+        ) {
+            let mut ma = Ma::new(period);
+            let mut stdev = Stdev::new(period);
+            let mut sample = Stdev::sample(period);
+            let mut cov = Cov::new(period);
+            let mut corr = Corr::new(period);
+
+            for (i, &(x, y)) in data.iter().enumerate() {
+                let window = window(&data, i, period);
+                let (mean, population, population_y, covariance, correlation) = naive(window, 0);
+                let (_, deviation, _, _, _) = naive(window, 1);
+
+                prop_assert!(close(ma.run(x), mean, 1e3));
+                prop_assert!(close(stdev.run(x), population, 1e3));
+                prop_assert!(close(sample.run(x), deviation, 1e3));
+                prop_assert!(close(cov.run(x, y), covariance, 1e6));
+
+                let r = corr.run(x, y);
+                prop_assert!((-1.0..=1.0).contains(&r));
+                if population > 1e-2 && population_y > 1e-2 {
+                    prop_assert!(close(r, correlation, 10.0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn drift() {
+        // Long window over prices with a large offset, where an f32 running sum drifts.
+        let period = 172_800;
+        let mut ma = Ma::new(period);
+        let mut stdev = Stdev::new(period);
+        let data: Vec<Number> = (0..2 * period)
+            .map(|i| 40_000.0 + ((i * 7919) % 1000) as Number / 10.0)
+            .collect();
+        for &x in &data {
+            ma.update(x);
+            stdev.update(x);
+        }
+
+        let window: Vec<(Number, Number)> = data[period..].iter().map(|&x| (x, 0.0)).collect();
+        let (mean, deviation, _, _, _) = naive(&window, 0);
+        assert!(close(ma.value().unwrap(), mean, 1e3));
+        assert!((stdev.value().unwrap() as f64 - deviation).abs() < 1e-3 * deviation);
+    }
+}
@@ -1,32 +1,54 @@
+use super::{Indicator, KahanSum, Real, Ring};
 use crate::Number;
-use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
-pub struct Sma {
-    data: VecDeque<Number>,
-    sum: Number,
-    period: usize,
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Sma<T: Real = Number> {
+    data: Ring<T>,
+    sum: KahanSum<T::Acc>,
 }
 
-impl Sma {
+impl<T: Real> Sma<T> {
     pub fn new(period: usize) -> Self {
         debug_assert!(period >= 1);
 
         Self {
-            data: VecDeque::new(),
-            sum: 0.0,
-            period,
+            data: Ring::new(period),
+            sum: KahanSum::new(),
         }
     }
 
-    pub fn run(&mut self, input: Number) -> Number {
-        self.data.push_back(input);
-        self.sum += input;
-        if self.data.len() > self.period as usize {
-            let sub = self.data.pop_front().unwrap();
-            self.sum -= sub;
+    pub fn run(&mut self, input: T) -> T {
+        if let Some(sub) = self.data.push(input) {
+            self.sum.add(-sub.to_acc());
         }
-        self.sum / self.data.len() as Number
+        self.sum.add(input.to_acc());
+        T::from_acc(self.sum.get() / T::Acc::from_usize(self.data.len()))
+    }
+}
+
+impl<T: Real> Indicator for Sma<T> {
+    type Input = T;
+    type Output = T;
+
+    fn update(&mut self, input: T) {
+        self.run(input);
+    }
+
+    fn value(&self) -> Option<T> {
+        if self.data.is_full() {
+            Some(T::from_acc(
+                self.sum.get() / T::Acc::from_usize(self.data.len()),
+            ))
+        } else {
+            None
+        }
+    }
+
+    fn reset(&mut self) {
+        self.data.clear();
+        self.sum.reset();
     }
 }
 
@@ -40,7 +62,13 @@ mod tests {
 
         assert_eq!(sma.run(2.0), 2.0);
         assert_eq!(sma.run(4.0), 3.0);
+        assert!(!sma.is_ready());
         assert_eq!(sma.run(6.0), 4.0);
+        assert_eq!(sma.value(), Some(4.0));
         assert_eq!(sma.run(8.0), 6.0);
+
+        sma.reset();
+        assert_eq!(sma.value(), None);
+        assert_eq!(sma.run(1.0), 1.0);
     }
 }
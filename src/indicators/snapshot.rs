@@ -0,0 +1,141 @@
+use super::{Chain, Fork, Map, Zip};
+use serde::{de::DeserializeOwned, Serialize};
+
+// State of an indicator that can be saved and restored later, to restart without warming up
+// again. Combinators only save the state of their indicators, not their functions.
+pub trait Snapshot {
+    type State: Serialize + DeserializeOwned;
+
+    fn snapshot(&self) -> Self::State;
+
+    fn restore(&mut self, state: Self::State);
+}
+
+// Indicators that are serializable themselves.
+macro_rules! snapshot {
+    ($($t:ident),*) => {
+        $(
+            impl<T: super::Real> Snapshot for super::$t<T> {
+                type State = Self;
+
+                fn snapshot(&self) -> Self {
+                    self.clone()
+                }
+
+                fn restore(&mut self, state: Self) {
+                    *self = state;
+                }
+            }
+        )*
+    };
+}
+
+snapshot!(Change, Corr, Cov, Cum, Diff, Ema, Ma, Norm, Stdev, Wilder, Window, Wma);
+
+impl<T: Copy + Serialize + DeserializeOwned> Snapshot for super::Last<T> {
+    type State = Self;
+
+    fn snapshot(&self) -> Self {
+        *self
+    }
+
+    fn restore(&mut self, state: Self) {
+        *self = state;
+    }
+}
+
+impl<T: Copy + Serialize + DeserializeOwned> Snapshot for super::Lag<T> {
+    type State = Self;
+
+    fn snapshot(&self) -> Self {
+        self.clone()
+    }
+
+    fn restore(&mut self, state: Self) {
+        *self = state;
+    }
+}
+
+impl<I: Snapshot, F> Snapshot for Map<I, F> {
+    type State = I::State;
+
+    fn snapshot(&self) -> I::State {
+        self.inner().snapshot()
+    }
+
+    fn restore(&mut self, state: I::State) {
+        self.inner_mut().restore(state);
+    }
+}
+
+impl<A: Snapshot, B: Snapshot> Snapshot for Chain<A, B> {
+    type State = (A::State, B::State);
+
+    fn snapshot(&self) -> Self::State {
+        (self.first().snapshot(), self.second().snapshot())
+    }
+
+    fn restore(&mut self, (a, b): Self::State) {
+        let (first, second) = self.parts_mut();
+        first.restore(a);
+        second.restore(b);
+    }
+}
+
+impl<A: Snapshot, B: Snapshot, F> Snapshot for Zip<A, B, F> {
+    type State = (A::State, B::State);
+
+    fn snapshot(&self) -> Self::State {
+        let (a, b) = self.parts();
+        (a.snapshot(), b.snapshot())
+    }
+
+    fn restore(&mut self, (a, b): Self::State) {
+        let (first, second) = self.parts_mut();
+        first.restore(a);
+        second.restore(b);
+    }
+}
+
+impl<A: Snapshot, B: Snapshot, F> Snapshot for Fork<A, B, F> {
+    type State = (A::State, B::State);
+
+    fn snapshot(&self) -> Self::State {
+        let (a, b) = self.parts();
+        (a.snapshot(), b.snapshot())
+    }
+
+    fn restore(&mut self, (a, b): Self::State) {
+        let (first, second) = self.parts_mut();
+        first.restore(a);
+        second.restore(b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators::{Change, Cum, Indicator, Last, Ma};
+
+    #[test]
+    fn restore() {
+        let new = || {
+            Change::new()
+                .chain(Cum::new())
+                .chain(Last::new().fork(Ma::new(3), |cum: f32, mean| cum - mean))
+        };
+        let (mut a, mut b) = (new(), new());
+        for &x in &[10.0, 12.0, 11.0, 13.0] {
+            a.update(x);
+        }
+
+        let bytes = serde_cbor::to_vec(&a.snapshot()).unwrap();
+        b.restore(serde_cbor::from_slice(&bytes).unwrap());
+        assert_eq!(a.value(), b.value());
+        for &x in &[9.0, 15.0] {
+            a.update(x);
+            b.update(x);
+        }
+        assert_eq!(a.value(), b.value());
+    }
+}
@@ -1,22 +1,57 @@
-use super::Ma;
+use super::{Indicator, Real, Window};
 use crate::Number;
+use serde::{Deserialize, Serialize};
 
-pub struct Stdev {
-    var: Ma,
-    mean: Ma,
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Stdev<T: Real = Number> {
+    window: Window<T>,
+    ddof: usize,
 }
 
-impl Stdev {
+impl<T: Real> Stdev<T> {
+    // Population standard deviation of the window.
     pub fn new(period: usize) -> Self {
+        debug_assert!(period >= 1);
+
+        Self {
+            window: Window::new(period),
+            ddof: 0,
+        }
+    }
+
+    // Sample standard deviation of the window, with Bessel's correction.
+    pub fn sample(period: usize) -> Self {
         Self {
-            var: Ma::new(period),
-            mean: Ma::new(period),
+            ddof: 1,
+            ..Self::new(period)
+        }
+    }
+
+    pub fn run(&mut self, input: T) -> T {
+        self.window.push(input);
+        T::from_acc(self.window.var(self.ddof).sqrt())
+    }
+}
+
+impl<T: Real> Indicator for Stdev<T> {
+    type Input = T;
+    type Output = T;
+
+    fn update(&mut self, input: T) {
+        self.run(input);
+    }
+
+    fn value(&self) -> Option<T> {
+        if self.window.is_full() {
+            Some(T::from_acc(self.window.var(self.ddof).sqrt()))
+        } else {
+            None
         }
     }
 
-    pub fn run(&mut self, input: Number) -> Number {
-        let mean = self.mean.run(input);
-        self.var.run((input - mean).powi(2)).sqrt()
+    fn reset(&mut self) {
+        self.window.reset();
     }
 }
 
@@ -31,4 +66,16 @@ mod tests {
         assert_eq!(stdev.run(100.0), 0.0);
         assert!(stdev.run(10.0) > 0.0);
     }
+
+    #[test]
+    fn sample() {
+        let mut population = Stdev::new(2);
+        let mut sample = Stdev::sample(2);
+        for &x in &[5.0, 1.0, 3.0] {
+            population.update(x);
+            sample.update(x);
+        }
+        assert_eq!(population.value(), Some(1.0));
+        assert_eq!(sample.value(), Some(2.0f32.sqrt()));
+    }
 }
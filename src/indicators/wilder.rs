@@ -0,0 +1,68 @@
+use super::{Indicator, Real};
+use crate::Number;
+use serde::{Deserialize, Serialize};
+
+// Wilder's smoothing as used by RSI and ATR, seeded with the simple average of the first period.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Wilder<T = Number> {
+    period: usize,
+    count: usize,
+    output: T,
+}
+
+impl<T: Real> Wilder<T> {
+    pub fn new(period: usize) -> Self {
+        debug_assert!(period >= 1);
+
+        Self {
+            period,
+            count: 0,
+            output: T::zero(),
+        }
+    }
+
+    pub fn run(&mut self, input: T) -> T {
+        self.count += 1;
+        let n = T::from_usize(self.count.min(self.period));
+        self.output += (input - self.output) / n;
+        self.output
+    }
+}
+
+impl<T: Real> Indicator for Wilder<T> {
+    type Input = T;
+    type Output = T;
+
+    fn update(&mut self, input: T) {
+        self.run(input);
+    }
+
+    fn value(&self) -> Option<T> {
+        if self.count >= self.period {
+            Some(self.output)
+        } else {
+            None
+        }
+    }
+
+    fn reset(&mut self) {
+        self.count = 0;
+        self.output = T::zero();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic() {
+        let mut wilder = Wilder::new(2);
+
+        assert_eq!(wilder.run(2.0), 2.0);
+        assert_eq!(wilder.value(), None);
+        assert_eq!(wilder.run(4.0), 3.0);
+        assert_eq!(wilder.run(7.0), 5.0);
+        assert_eq!(wilder.value(), Some(5.0));
+    }
+}
@@ -0,0 +1,78 @@
+use super::{Indicator, Real, Ring};
+use crate::Number;
+use serde::{Deserialize, Serialize};
+
+// Linearly weighted moving average, the newest input has the highest weight.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Wma<T = Number> {
+    data: Ring<T>,
+    sum: T,
+    weighted: T,
+}
+
+impl<T: Real> Wma<T> {
+    pub fn new(period: usize) -> Self {
+        debug_assert!(period >= 1);
+
+        Self {
+            data: Ring::new(period),
+            sum: T::zero(),
+            weighted: T::zero(),
+        }
+    }
+
+    pub fn run(&mut self, input: T) -> T {
+        if self.data.is_full() {
+            // Every remaining input loses one weight.
+            self.weighted -= self.sum;
+        }
+        if let Some(sub) = self.data.push(input) {
+            self.sum -= sub;
+        }
+        self.sum += input;
+        let n = self.data.len();
+        self.weighted += T::from_usize(n) * input;
+        self.weighted / T::from_usize(n * (n + 1) / 2)
+    }
+}
+
+impl<T: Real> Indicator for Wma<T> {
+    type Input = T;
+    type Output = T;
+
+    fn update(&mut self, input: T) {
+        self.run(input);
+    }
+
+    fn value(&self) -> Option<T> {
+        if self.data.is_full() {
+            let n = self.data.len();
+            Some(self.weighted / T::from_usize(n * (n + 1) / 2))
+        } else {
+            None
+        }
+    }
+
+    fn reset(&mut self) {
+        self.data.clear();
+        self.sum = T::zero();
+        self.weighted = T::zero();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic() {
+        let mut wma = Wma::new(3);
+
+        assert_eq!(wma.run(3.0), 3.0);
+        assert_eq!(wma.run(6.0), 5.0);
+        assert_eq!(wma.value(), None);
+        assert_eq!(wma.run(9.0), 7.0);
+        assert_eq!(wma.run(3.0), 5.5);
+        assert_eq!(wma.value(), Some(5.5));
+    }
+}
@@ -0,0 +1,237 @@
+use crate::{
+    trader::{Coin, Exit},
+    Number,
+};
+use chrono::{DateTime, Utc};
+use futures::future::poll_fn;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::Cell,
+    fs::OpenOptions,
+    future::Future,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+thread_local! {
+    static SILENT: Cell<bool> = const { Cell::new(false) };
+}
+
+// Silences the current thread until dropped.
+struct Silence(bool);
+
+impl Silence {
+    fn new() -> Self {
+        Silence(SILENT.with(|silent| silent.replace(true)))
+    }
+}
+
+impl Drop for Silence {
+    fn drop(&mut self) {
+        SILENT.with(|silent| silent.set(self.0));
+    }
+}
+
+// Runs the future without recording events or sending alerts, for backtests that run next to
+// each other like those of the optimizer.
+pub async fn silently<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    poll_fn(|cx| {
+        let _silence = Silence::new();
+        future.as_mut().poll(cx)
+    })
+    .await
+}
+
+pub fn is_silent() -> bool {
+    SILENT.with(Cell::get)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rejection {
+    AlreadyInvested,
+    NoFunds,
+    RiskLimit,
+    Paused,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Signal {
+        time: DateTime<Utc>,
+        long: Coin,
+        short: Coin,
+        corr: Number,
+        diff: Number,
+        stdev: Number,
+    },
+    Open {
+        time: DateTime<Utc>,
+        long: Coin,
+        short: Coin,
+        long_price: Decimal,
+        short_price: Decimal,
+        amount: Decimal,
+        fees: Decimal,
+    },
+    Close {
+        time: DateTime<Utc>,
+        long: Coin,
+        short: Coin,
+        long_price: Decimal,
+        short_price: Decimal,
+        exit: Exit,
+        fees: Decimal,
+        profit: Decimal,
+    },
+    Rejected {
+        time: DateTime<Utc>,
+        long: Coin,
+        short: Coin,
+        reason: Rejection,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    // One JSON object per line.
+    Json,
+    // Concatenated CBOR values.
+    Cbor,
+}
+
+impl Format {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("cbor") => Format::Cbor,
+            _ => Format::Json,
+        }
+    }
+}
+
+// Records events to a file, does nothing until opened.
+pub struct Journal {
+    writer: Mutex<Option<(Format, Box<dyn Write + Send>)>>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Journal {
+            writer: Mutex::new(None),
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())?;
+        self.to_writer(Format::from_path(path), BufWriter::new(file));
+        Ok(())
+    }
+
+    pub fn to_writer<W: Write + Send + 'static>(&self, format: Format, writer: W) {
+        *self.writer.lock().unwrap() = Some((format, Box::new(writer)));
+    }
+
+    pub fn record(&self, event: Event) {
+        if is_silent() {
+            return;
+        }
+        if let Some((format, writer)) = self.writer.lock().unwrap().as_mut() {
+            let result = match format {
+                Format::Json => serde_json::to_writer(&mut *writer, &event)
+                    .map_err(io::Error::from)
+                    .and_then(|_| writeln!(writer)),
+                Format::Cbor => serde_cbor::to_writer(&mut *writer, &event)
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err)),
+            };
+            if let Err(err) = result.and_then(|_| writer.flush()) {
+                log::error!("Could not write to journal: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn event() -> Event {
+        Event::Rejected {
+            time: Utc::now(),
+            long: Coin::BTC,
+            short: Coin::ETH,
+            reason: Rejection::NoFunds,
+        }
+    }
+
+    #[test]
+    fn json() {
+        let buf = Shared(Arc::new(Mutex::new(Vec::new())));
+        let journal = Journal::new();
+        journal.record(event());
+        journal.to_writer(Format::Json, buf.clone());
+        journal.record(event());
+        journal.record(event());
+
+        let out = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(out.lines().count(), 2);
+        let event: Event = serde_json::from_str(out.lines().next().unwrap()).unwrap();
+        assert!(matches!(
+            event,
+            Event::Rejected {
+                reason: Rejection::NoFunds,
+                ..
+            }
+        ));
+        assert!(out.contains(r#""event":"rejected""#));
+    }
+
+    #[test]
+    fn cbor() {
+        let buf = Shared(Arc::new(Mutex::new(Vec::new())));
+        let journal = Journal::new();
+        journal.to_writer(Format::Cbor, buf.clone());
+        journal.record(event());
+        journal.record(event());
+
+        let bytes = buf.0.lock().unwrap().clone();
+        let events: Vec<Event> = serde_cbor::Deserializer::from_slice(&bytes)
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(events.len(), 2);
+    }
+
+    #[test]
+    fn silent() {
+        let buf = Shared(Arc::new(Mutex::new(Vec::new())));
+        let journal = Journal::new();
+        journal.to_writer(Format::Json, buf.clone());
+        futures::executor::block_on(silently(async { journal.record(event()) }));
+        assert!(!is_silent());
+        journal.record(event());
+
+        let out = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(out.lines().count(), 1);
+    }
+}
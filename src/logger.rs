@@ -1,7 +0,0 @@
-pub struct Logger {}
-
-impl Logger {
-    pub fn new() -> Self {
-        Logger {}
-    }
-}
@@ -1,28 +1,300 @@
+#[cfg(feature = "api")]
+mod api;
+#[cfg(feature = "plot")]
+mod chart;
+mod cli;
+mod control;
 mod indicators;
-mod logger;
+mod journal;
+mod metrics;
+mod notifier;
+mod recorder;
 mod trader;
 
-// Either f32 or f64.
+// Default number type of the indicators, either f32 or f64.
 type Number = f32;
 
-use chrono::{Duration, TimeZone, Utc};
-use logger::Logger;
+use chrono::Duration;
+use cli::{Command, Opt};
+use control::Control;
+use indicators::Real;
+use journal::Journal;
+use metrics::Metrics;
+use notifier::Notifier;
 use once_cell::sync::Lazy;
-use trader::{Coin, Trader};
+use rust_decimal::Decimal;
+use std::fs::{create_dir_all, File};
+use structopt::StructOpt;
+use trader::{
+    Columns, Config, Exchange, Fetcher, Investor, Manifest, Mode, Paper, Precision, Report, Search,
+    Trader, WalkForward,
+};
 
-#[allow(dead_code)]
-static LOGGER: Lazy<Logger> = Lazy::new(|| Logger::new());
+static JOURNAL: Lazy<Journal> = Lazy::new(|| Journal::new());
+static METRICS: Lazy<Metrics> = Lazy::new(|| Metrics::new());
+static NOTIFIER: Lazy<Notifier> = Lazy::new(|| Notifier::new());
+static CONTROL: Lazy<Control> = Lazy::new(|| Control::new());
+#[cfg(feature = "api")]
+static API: Lazy<api::Api> = Lazy::new(|| api::Api::new());
+
+// Fails the command, for errors in the files it reads or writes.
+fn fail<T>(err: String) -> T {
+    log::error!("Could not run the command: {}", err);
+    std::process::exit(1);
+}
 
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().ok();
     pretty_env_logger::init();
 
-    let coins = Coin::all();
-    let trader = Trader::new(
-        &coins,
-        Utc.ymd(2021, 2, 1).and_hms(0, 0, 0),
-        Duration::seconds(15),
+    let opt = Opt::from_args();
+    let mut config = opt
+        .config
+        .as_ref()
+        .map(Config::load)
+        .transpose()
+        .unwrap_or_else(fail)
+        .unwrap_or_default();
+    if let Some(precision) = opt.precision {
+        config.precision = precision;
+    }
+    create_dir_all(&opt.out)
+        .map_err(|err| format!("{:?}: {}", opt.out, err))
+        .unwrap_or_else(fail);
+    if let Some(threads) = opt.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .unwrap();
+    }
+    if let Some(path) = &opt.journal {
+        JOURNAL.open(path).unwrap();
+    }
+    if let Some(addr) = opt.metrics {
+        tokio::spawn(metrics::serve(addr));
+    }
+    if let Some(addr) = opt.control {
+        let token = control::token(&addr).unwrap_or_else(|err| {
+            log::error!("Could not start control server: {}", err);
+            std::process::exit(1);
+        });
+        CONTROL.open_audit(opt.out.join("audit.jsonl")).unwrap();
+        tokio::spawn(control::serve(addr, token));
+    }
+    #[cfg(feature = "api")]
+    if let Some(addr) = opt.api {
+        tokio::spawn(api::serve(addr));
+    }
+
+    match config.precision {
+        Precision::F32 => run::<f32>(opt, config).await,
+        Precision::F64 => run::<f64>(opt, config).await,
+        Precision::Decimal => run::<Decimal>(opt, config).await,
+    }
+}
+
+async fn run<T: Real>(opt: Opt, config: Config) {
+    let coins = opt.coins();
+    let recorder = opt.recorder();
+    let from = opt.start(&config.params);
+    let mut fetcher = Fetcher::new(&coins, from, Duration::seconds(opt.interval));
+    if let Some(to) = opt.to {
+        fetcher = fetcher.until(to);
+    }
+    // Historical data comes from the cache, or is generated from a scenario.
+    let source = opt.source();
+
+    match opt.command {
+        Command::Backtest => {
+            let fetcher = source.historical(fetcher).unwrap_or_else(fail);
+            let mut trader =
+                Trader::<T>::new(&coins, fetcher, &config, Mode::Backtest).record(&recorder);
+            let report = trader.run().await;
+            let file = File::create(opt.out.join("report.json")).unwrap();
+            serde_json::to_writer_pretty(file, &report).unwrap();
+            trader.export(&opt.out).unwrap();
+            Manifest::new(
+                &config,
+                &coins,
+                (from, opt.to),
+                opt.interval,
+                &source,
+                trader.investor(),
+            )
+            .save(opt.out.join("manifest.json"))
+            .unwrap();
+
+            #[cfg(feature = "plot")]
+            for &(long, short) in recorder.pairs() {
+                chart::plot_pair(
+                    recorder.pair_path(long, short),
+                    long,
+                    short,
+                    &config.params,
+                    trader.investor().positions(),
+                    opt.out.join(format!("pair_{}_{}.png", long, short)),
+                )
+                .unwrap();
+            }
+        }
+        Command::Paper { state } => {
+            NOTIFIER.start(&config.alerts);
+            let paper = Paper::new(state);
+            let investor = paper.load().map_err(|err| err.to_string());
+            let mut trader =
+                Trader::<T>::new(&coins, fetcher, &config, Mode::Paper(paper)).record(&recorder);
+            if let Some(investor) = investor.unwrap_or_else(fail) {
+                trader = trader.with_investor(investor);
+            }
+            if let Some(path) = &opt.snapshot {
+                trader = trader.snapshot(path);
+            }
+            trader.run().await;
+        }
+        Command::Live => {
+            NOTIFIER.start(&config.alerts);
+            let exchange = Exchange::from_env().with_fee(config.costs.fee);
+            let mut trader =
+                Trader::<T>::new(&coins, fetcher, &config, Mode::Live(exchange)).record(&recorder);
+            if let Some(path) = &opt.snapshot {
+                trader = trader.snapshot(path);
+            }
+            trader.run().await;
+        }
+        Command::FetchData => {
+            fetcher
+                .cached(&opt.cache)
+                .refresh(&trader::lazy_rest())
+                .await;
+        }
+        Command::Import {
+            files,
+            time_column,
+            close_column,
+            volume_column,
+            coin_column,
+            time_format,
+        } => {
+            let columns = Columns {
+                time: time_column,
+                close: close_column,
+                volume: volume_column,
+                coin: coin_column,
+                format: time_format,
+            };
+            // Imports all coins in the files unless some are given.
+            if let Err(err) = trader::import(
+                &files,
+                &columns,
+                &opt.coins,
+                Duration::seconds(opt.interval),
+                (from, opt.to),
+                &opt.cache,
+            ) {
+                log::error!("Could not import candles: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::Replay { manifest } => {
+            let manifest = Manifest::load(&manifest)
+                .map_err(|err| format!("{:?}: {}", manifest, err))
+                .unwrap_or_else(fail);
+            // Replays with the precision of the manifest, whatever is configured.
+            match manifest.config.precision {
+                Precision::F32 => replay::<f32>(manifest).await,
+                Precision::F64 => replay::<f64>(manifest).await,
+                Precision::Decimal => replay::<Decimal>(manifest).await,
+            }
+        }
+        Command::Report { state } => {
+            // The state only keeps the current wallet, the equity of all runs is read separately.
+            let paper = Paper::new(state);
+            let investor = paper
+                .load()
+                .map_err(|err| err.to_string())
+                .unwrap_or_else(fail)
+                .unwrap_or_else(Investor::new);
+            let equity = paper
+                .equity()
+                .map_err(|err| err.to_string())
+                .unwrap_or_else(fail);
+            Report::with_equity(&investor, &equity).log();
+        }
+        Command::Optimize {
+            samples,
+            seed,
+            walk_forward,
+        } => {
+            let search = match samples {
+                Some(samples) => Search::Random {
+                    grid: config.grid.clone(),
+                    samples,
+                    seed: seed.unwrap_or(config.seed),
+                },
+                None => Search::Grid(config.grid.clone()),
+            };
+
+            let fetcher = source.historical(fetcher).unwrap_or_else(fail);
+            let trader = Trader::<T>::new(&coins, fetcher, &config, Mode::Backtest);
+            let written = if let Some(days) = walk_forward {
+                let walk_forward =
+                    WalkForward::new(Duration::days(days[0]), Duration::days(days[1]));
+                trader
+                    .walk_forward(search, walk_forward, &opt.out)
+                    .await
+                    .map(drop)
+            } else {
+                trader.optimize(search, &opt.out).await.map(drop)
+            };
+            written
+                .map_err(|err| format!("{:?}: {}", opt.out, err))
+                .unwrap_or_else(fail);
+        }
+    }
+}
+
+async fn replay<T: Real>(manifest: Manifest) {
+    // Never downloads a missing cache, as that would replay on other candles.
+    manifest.check_source().unwrap_or_else(fail);
+    let mut fetcher = Fetcher::new(
+        &manifest.coins,
+        manifest.from,
+        Duration::seconds(manifest.interval),
     );
-    trader.run().await
+    if let Some(to) = manifest.to {
+        fetcher = fetcher.until(to);
+    }
+    let mut trader = Trader::<T>::new(
+        &manifest.coins,
+        manifest.source.historical(fetcher).unwrap_or_else(fail),
+        &manifest.config,
+        Mode::Backtest,
+    );
+    trader.run().await;
+    let replay = Manifest::new(
+        &manifest.config,
+        &manifest.coins,
+        (manifest.from, manifest.to),
+        manifest.interval,
+        &manifest.source,
+        trader.investor(),
+    );
+
+    if manifest.commit != replay.commit {
+        log::warn!(
+            "Replaying {:?} with code version {:?}.",
+            manifest.commit,
+            replay.commit
+        );
+    }
+    let differences = manifest.verify(&replay);
+    if differences.is_empty() {
+        log::info!("REPLAY: 	identical, {} trades", replay.trades);
+    } else {
+        for difference in &differences {
+            log::error!("REPLAY: 	{}", difference);
+        }
+        std::process::exit(1);
+    }
 }
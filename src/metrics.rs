@@ -0,0 +1,234 @@
+use crate::trader::Coin;
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use std::{
+    convert::Infallible,
+    fmt::Write,
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+#[derive(Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Floating point value stored as its bits.
+#[derive(Default)]
+pub struct Gauge(AtomicU64);
+
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn add(&self, value: f64) {
+        let mut current = self.0.load(Ordering::Relaxed);
+        loop {
+            let new = (f64::from_bits(current) + value).to_bits();
+            match self
+                .0
+                .compare_exchange_weak(current, new, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+pub struct PerCoin<T>(Vec<T>);
+
+impl<T: Default> PerCoin<T> {
+    fn new() -> Self {
+        PerCoin(Coin::all().iter().map(|_| T::default()).collect())
+    }
+}
+
+impl<T> PerCoin<T> {
+    pub fn coin(&self, coin: Coin) -> &T {
+        &self.0[coin as usize]
+    }
+}
+
+pub struct Metrics {
+    pub candles: PerCoin<Counter>,
+    pub fetches: PerCoin<Counter>,
+    pub fetch_errors: PerCoin<Counter>,
+    pub fetch_seconds: PerCoin<Gauge>,
+    pub signals: Counter,
+    pub positions_open: Gauge,
+    pub realized_profit: Gauge,
+    pub unrealized_profit: Gauge,
+    pub balance: Gauge,
+    pub free_parts: Gauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics {
+            candles: PerCoin::new(),
+            fetches: PerCoin::new(),
+            fetch_errors: PerCoin::new(),
+            fetch_seconds: PerCoin::new(),
+            signals: Counter::default(),
+            positions_open: Gauge::default(),
+            realized_profit: Gauge::default(),
+            unrealized_profit: Gauge::default(),
+            balance: Gauge::default(),
+            free_parts: Gauge::default(),
+        }
+    }
+
+    // Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let mut per_coin = |name: &str, kind: &str, help: &str, value: &dyn Fn(Coin) -> String| {
+            writeln!(out, "# HELP {} {}", name, help).unwrap();
+            writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+            for coin in Coin::all() {
+                writeln!(out, "{}{{coin=\"{}\"}} {}", name, coin, value(coin)).unwrap();
+            }
+        };
+        per_coin(
+            "trader_candles_total",
+            "counter",
+            "Candles processed.",
+            &|coin| self.candles.coin(coin).get().to_string(),
+        );
+        per_coin(
+            "trader_fetches_total",
+            "counter",
+            "Requests for historical prices.",
+            &|coin| self.fetches.coin(coin).get().to_string(),
+        );
+        per_coin(
+            "trader_fetch_errors_total",
+            "counter",
+            "Failed requests for historical prices.",
+            &|coin| self.fetch_errors.coin(coin).get().to_string(),
+        );
+        per_coin(
+            "trader_fetch_seconds_total",
+            "counter",
+            "Time spent requesting historical prices.",
+            &|coin| self.fetch_seconds.coin(coin).get().to_string(),
+        );
+
+        let mut single = |name: &str, kind: &str, help: &str, value: String| {
+            writeln!(out, "# HELP {} {}", name, help).unwrap();
+            writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+            writeln!(out, "{} {}", name, value).unwrap();
+        };
+        single(
+            "trader_signals_total",
+            "counter",
+            "Entry signals generated by the strategy.",
+            self.signals.get().to_string(),
+        );
+        single(
+            "trader_positions_open",
+            "gauge",
+            "Currently open positions.",
+            self.positions_open.get().to_string(),
+        );
+        single(
+            "trader_realized_profit",
+            "gauge",
+            "Realized profit of all closed positions.",
+            self.realized_profit.get().to_string(),
+        );
+        single(
+            "trader_unrealized_profit",
+            "gauge",
+            "Unrealized profit of all open positions.",
+            self.unrealized_profit.get().to_string(),
+        );
+        single(
+            "trader_wallet_balance",
+            "gauge",
+            "Cash not invested in positions.",
+            self.balance.get().to_string(),
+        );
+        single(
+            "trader_wallet_free_parts",
+            "gauge",
+            "Parts of the wallet available for new positions.",
+            self.free_parts.get().to_string(),
+        );
+
+        out
+    }
+}
+
+async fn handle(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let response = match req.uri().path() {
+        "/metrics" => Response::builder()
+            .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(Body::from(crate::METRICS.render())),
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    };
+    Ok(response.unwrap())
+}
+
+pub async fn serve(addr: SocketAddr) {
+    let server = match Server::try_bind(&addr) {
+        Ok(server) => server,
+        Err(err) => {
+            log::error!("Could not serve metrics on {}: {}", addr, err);
+            return;
+        }
+    };
+    log::info!("Serving metrics on http://{}/metrics.", addr);
+    let service = make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(handle)) });
+    if let Err(err) = server.serve(service).await {
+        log::error!("Metrics server failed: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let metrics = Metrics::new();
+        metrics.candles.coin(Coin::ETH).inc();
+        metrics.candles.coin(Coin::ETH).inc();
+        metrics.fetch_seconds.coin(Coin::BTC).add(0.5);
+        metrics.fetch_seconds.coin(Coin::BTC).add(0.25);
+        metrics.balance.set(42.5);
+
+        let out = metrics.render();
+        assert!(out.contains("trader_candles_total{coin=\"ETH-PERP\"} 2\n"));
+        assert!(out.contains("trader_candles_total{coin=\"BTC-PERP\"} 0\n"));
+        assert!(out.contains("trader_fetch_seconds_total{coin=\"BTC-PERP\"} 0.75\n"));
+        assert!(out.contains("trader_wallet_balance 42.5\n"));
+        assert!(out.contains("# TYPE trader_positions_open gauge\n"));
+    }
+
+    #[tokio::test]
+    async fn port_in_use() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        // Returns instead of panicking.
+        serve(listener.local_addr().unwrap()).await;
+    }
+}
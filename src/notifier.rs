@@ -0,0 +1,367 @@
+use crate::journal;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use lettre::{
+    transport::smtp::authentication::Credentials, AsyncSmtpTransport, AsyncTransport, Message,
+    Tokio1Executor,
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env::var,
+    fmt,
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "INFO"),
+            Severity::Warning => write!(f, "WARNING"),
+            Severity::Critical => write!(f, "CRITICAL"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    pub time: DateTime<Utc>,
+    pub severity: Severity,
+    pub kind: String,
+    pub message: String,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}",
+            self.time.to_rfc3339(),
+            self.severity,
+            self.kind,
+            self.message
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Smtp {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Alerts {
+    // Alerts below this severity are dropped.
+    pub severity: Severity,
+    // Seconds between two alerts of the same kind.
+    pub rate_limit: u64,
+    // Loss of a single position that is reported as critical.
+    pub large_loss: Decimal,
+    pub stdout: bool,
+    pub file: Option<PathBuf>,
+    pub webhook: Option<String>,
+    pub smtp: Option<Smtp>,
+}
+
+impl Default for Alerts {
+    fn default() -> Self {
+        Alerts {
+            severity: Severity::Warning,
+            rate_limit: 300,
+            large_loss: Decimal::new(5, 0),
+            stdout: false,
+            file: None,
+            webhook: None,
+            smtp: None,
+        }
+    }
+}
+
+pub type SinkResult = Result<(), String>;
+
+#[async_trait]
+pub trait Sink: Send + Sync {
+    async fn send(&self, alert: &Alert) -> SinkResult;
+}
+
+pub struct Stdout;
+
+#[async_trait]
+impl Sink for Stdout {
+    async fn send(&self, alert: &Alert) -> SinkResult {
+        println!("{}", alert);
+        Ok(())
+    }
+}
+
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        FileSink { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl Sink for FileSink {
+    async fn send(&self, alert: &Alert) -> SinkResult {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|err| err.to_string())?;
+        writeln!(file, "{}", alert).map_err(|err| err.to_string())
+    }
+}
+
+// Posts the alert as JSON to the given URL.
+pub struct Webhook {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl Webhook {
+    pub fn new(url: &str) -> Self {
+        Webhook {
+            url: url.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for Webhook {
+    async fn send(&self, alert: &Alert) -> SinkResult {
+        self.client
+            .post(&self.url)
+            .json(alert)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+}
+
+pub struct Email {
+    from: String,
+    to: String,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl Email {
+    // The password is read from the SMTP_PASSWORD environment variable.
+    pub fn new(smtp: &Smtp) -> Result<Self, String> {
+        let password = var("SMTP_PASSWORD").map_err(|_| "SMTP_PASSWORD is not set".to_string())?;
+        let credentials = Credentials::new(smtp.username.clone(), password);
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)
+            .map_err(|err| err.to_string())?
+            .port(smtp.port)
+            .credentials(credentials)
+            .build();
+        Ok(Email {
+            from: smtp.from.clone(),
+            to: smtp.to.clone(),
+            transport,
+        })
+    }
+}
+
+#[async_trait]
+impl Sink for Email {
+    async fn send(&self, alert: &Alert) -> SinkResult {
+        let message = Message::builder()
+            .from(self.from.parse().map_err(|err| format!("{}", err))?)
+            .to(self.to.parse().map_err(|err| format!("{}", err))?)
+            .subject(format!("[{}] {}", alert.severity, alert.kind))
+            .body(alert.to_string())
+            .map_err(|err| err.to_string())?;
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+}
+
+// Allows one alert per kind, severity and interval, so a less severe alert never suppresses a
+// more severe one of the same kind.
+pub struct RateLimiter {
+    interval: Duration,
+    last: HashMap<(String, Severity), Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration) -> Self {
+        RateLimiter {
+            interval,
+            last: HashMap::new(),
+        }
+    }
+
+    pub fn allow(&mut self, kind: &str, severity: Severity, now: Instant) -> bool {
+        let key = (kind.to_string(), severity);
+        match self.last.get(&key) {
+            Some(&last) if now.duration_since(last) < self.interval => false,
+            _ => {
+                self.last.insert(key, now);
+                true
+            }
+        }
+    }
+}
+
+struct State {
+    config: Alerts,
+    limiter: RateLimiter,
+    sender: Option<UnboundedSender<Alert>>,
+}
+
+pub struct Notifier {
+    state: Mutex<State>,
+}
+
+impl Notifier {
+    // Drops all alerts until started.
+    pub fn new() -> Self {
+        let config = Alerts::default();
+        Notifier {
+            state: Mutex::new(State {
+                limiter: RateLimiter::new(Duration::from_secs(config.rate_limit)),
+                config,
+                sender: None,
+            }),
+        }
+    }
+
+    // Delivers alerts to the configured sinks in the background.
+    pub fn start(&self, config: &Alerts) {
+        let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+        if config.stdout {
+            sinks.push(Box::new(Stdout));
+        }
+        if let Some(path) = &config.file {
+            sinks.push(Box::new(FileSink::new(path)));
+        }
+        if let Some(url) = &config.webhook {
+            sinks.push(Box::new(Webhook::new(url)));
+        }
+        if let Some(smtp) = &config.smtp {
+            match Email::new(smtp) {
+                Ok(email) => sinks.push(Box::new(email)),
+                Err(err) => log::error!("Could not set up email alerts: {}", err),
+            }
+        }
+
+        let (sender, mut receiver) = unbounded_channel::<Alert>();
+        tokio::spawn(async move {
+            while let Some(alert) = receiver.recv().await {
+                for sink in &sinks {
+                    if let Err(err) = sink.send(&alert).await {
+                        log::error!("Could not send alert: {}", err);
+                    }
+                }
+            }
+        });
+
+        let mut state = self.state.lock().unwrap();
+        state.limiter = RateLimiter::new(Duration::from_secs(config.rate_limit));
+        state.config = config.clone();
+        state.sender = Some(sender);
+    }
+
+    pub fn is_large_loss(&self, profit: Decimal) -> bool {
+        profit <= -self.state.lock().unwrap().config.large_loss
+    }
+
+    pub fn notify(&self, severity: Severity, kind: &str, message: String) {
+        if journal::is_silent() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        let sender = match &state.sender {
+            Some(sender) if severity >= state.config.severity => sender.clone(),
+            _ => return,
+        };
+        if !state.limiter.allow(kind, severity, Instant::now()) {
+            log::debug!("Rate limited alert {}: {}", kind, message);
+            return;
+        }
+
+        sender
+            .send(Alert {
+                time: Utc::now(),
+                severity,
+                kind: kind.to_string(),
+                message,
+            })
+            .ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env::temp_dir, fs::read_to_string};
+
+    #[test]
+    fn rate_limit() {
+        let mut limiter = RateLimiter::new(Duration::from_secs(60));
+        let now = Instant::now();
+
+        let warning = Severity::Warning;
+        assert!(limiter.allow("gap", warning, now));
+        assert!(!limiter.allow("gap", warning, now + Duration::from_secs(30)));
+        assert!(limiter.allow("close", warning, now + Duration::from_secs(30)));
+        assert!(limiter.allow("gap", warning, now + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn rate_limit_severity() {
+        let mut limiter = RateLimiter::new(Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(limiter.allow("exchange", Severity::Warning, now));
+        assert!(limiter.allow("exchange", Severity::Critical, now + Duration::from_secs(1)));
+        assert!(!limiter.allow("exchange", Severity::Critical, now + Duration::from_secs(2)));
+        assert!(!limiter.allow("exchange", Severity::Warning, now + Duration::from_secs(2)));
+    }
+
+    #[tokio::test]
+    async fn file() {
+        let path = temp_dir().join(format!("alerts-{}.log", rand::random::<u64>()));
+
+        let alert = Alert {
+            time: Utc::now(),
+            severity: Severity::Critical,
+            kind: "close".to_string(),
+            message: "BTC-PERP/ETH-PERP lost 6.00".to_string(),
+        };
+        FileSink::new(&path).send(&alert).await.unwrap();
+
+        let line = read_to_string(&path).unwrap();
+        assert!(line.ends_with("\tCRITICAL\tclose\tBTC-PERP/ETH-PERP lost 6.00\n"));
+        assert!(Severity::Info < Severity::Warning);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{trader::Coin, Number};
+use chrono::{DateTime, Utc};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+// Time series of indicator values, written as one CSV column per value. A series that fails
+// to write is disabled, so a full disk never stops trading.
+pub struct Series {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    columns: usize,
+}
+
+impl Series {
+    pub fn create<P: AsRef<Path>>(path: P, columns: &[&str]) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writeln!(writer, "time,{}", columns.join(","))?;
+        Ok(Series {
+            path: path.as_ref().to_path_buf(),
+            writer: Some(writer),
+            columns: columns.len(),
+        })
+    }
+
+    pub fn record(&mut self, time: DateTime<Utc>, values: &[Number]) {
+        debug_assert_eq!(values.len(), self.columns);
+
+        self.write(|writer| {
+            write!(writer, "{}", time.to_rfc3339())?;
+            for value in values {
+                write!(writer, ",{}", value)?;
+            }
+            writeln!(writer)
+        });
+    }
+
+    pub fn flush(&mut self) {
+        self.write(|writer| writer.flush());
+    }
+
+    fn write<F: FnOnce(&mut BufWriter<File>) -> io::Result<()>>(&mut self, write: F) {
+        if let Some(writer) = &mut self.writer {
+            if let Err(err) = write(writer) {
+                log::error!(
+                    "Could not record {:?}, stopped recording: {}",
+                    self.path,
+                    err
+                );
+                self.writer = None;
+            }
+        }
+    }
+
+    // Fails on rows that are cut off or don't parse, as left behind by a failed write.
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<(DateTime<Utc>, Vec<Number>)>> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let columns = match lines.next() {
+            Some(header) => header?.split(',').count() - 1,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut rows = Vec::new();
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid row {}: {:?}", i + 1, line),
+                )
+            };
+            let mut values = line.split(',');
+            let time = values
+                .next()
+                .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+                .ok_or_else(invalid)?
+                .with_timezone(&Utc);
+            let values = values
+                .map(|value| value.parse())
+                .collect::<Result<Vec<Number>, _>>()
+                .map_err(|_| invalid())?;
+            if values.len() != columns {
+                return Err(invalid());
+            }
+            rows.push((time, values));
+        }
+        Ok(rows)
+    }
+}
+
+// Selects the coins and pairs whose indicators are recorded.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    dir: PathBuf,
+    coins: Vec<Coin>,
+    pairs: Vec<(Coin, Coin)>,
+}
+
+impl Recorder {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Recorder {
+            dir: dir.into(),
+            coins: Vec::new(),
+            pairs: Vec::new(),
+        }
+    }
+
+    pub fn coin(mut self, coin: Coin) -> Self {
+        self.coins.push(coin);
+        self
+    }
+
+    pub fn pair(mut self, long: Coin, short: Coin) -> Self {
+        self.pairs.push((long, short));
+        self
+    }
+
+    pub fn single_path(&self, coin: Coin) -> PathBuf {
+        self.dir.join(format!("single_{}.csv", coin))
+    }
+
+    pub fn pair_path(&self, long: Coin, short: Coin) -> PathBuf {
+        self.dir.join(format!("pair_{}_{}.csv", long, short))
+    }
+
+    pub fn pairs(&self) -> &[(Coin, Coin)] {
+        &self.pairs
+    }
+
+    pub fn single(&self, coin: Coin, columns: &[&str]) -> Option<Series> {
+        if self.coins.contains(&coin) {
+            create(self.single_path(coin), columns)
+        } else {
+            None
+        }
+    }
+
+    pub fn pair_series(&self, long: Coin, short: Coin, columns: &[&str]) -> Option<Series> {
+        if self.pairs.contains(&(long, short)) {
+            create(self.pair_path(long, short), columns)
+        } else {
+            None
+        }
+    }
+}
+
+// Leaves the series unrecorded if its file can't be created.
+fn create(path: PathBuf, columns: &[&str]) -> Option<Series> {
+    match Series::create(&path, columns) {
+        Ok(series) => Some(series),
+        Err(err) => {
+            log::error!("Could not record {:?}: {}", path, err);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn record() {
+        let dir = temp_dir().join(format!("recorder-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let recorder = Recorder::new(&dir)
+            .coin(Coin::BTC)
+            .pair(Coin::BTC, Coin::ETH);
+        assert!(recorder.single(Coin::ETH, &["a"]).is_none());
+        assert!(recorder.pair_series(Coin::ETH, Coin::BTC, &["a"]).is_none());
+
+        let time = Utc::now();
+        {
+            let mut series = recorder
+                .pair_series(Coin::BTC, Coin::ETH, &["a", "b"])
+                .unwrap();
+            series.record(time, &[1.0, 2.5]);
+            series.record(time, &[-1.0, 0.0]);
+        }
+
+        let rows = Series::read(recorder.pair_path(Coin::BTC, Coin::ETH)).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].1, vec![1.0, 2.5]);
+        assert_eq!(rows[1].0.timestamp(), time.timestamp());
+
+        // A row cut off by a full disk.
+        let path = recorder.pair_path(Coin::BTC, Coin::ETH);
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, &csv[..csv.len() - 3]).unwrap();
+        assert!(Series::read(&path).is_err());
+        assert!(Series::read(dir.join("missing.csv")).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_dir() {
+        let dir = temp_dir().join(format!("recorder-{}", rand::random::<u64>()));
+        let recorder = Recorder::new(&dir).coin(Coin::BTC);
+        // The directory doesn't exist, so the series can't be created.
+        assert!(recorder.single(Coin::BTC, &["a"]).is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn full_disk() {
+        // Writes to a full disk fail, and then stop.
+        let mut series = Series::create("/dev/full", &["a"]).unwrap();
+        series.record(Utc::now(), &[1.0]);
+        series.flush();
+        assert!(series.writer.is_none());
+        series.record(Utc::now(), &[2.0]);
+        series.flush();
+    }
+}
@@ -0,0 +1,254 @@
+use super::{Coin, Executor, Fill, Side};
+use crate::{notifier::Severity, NOTIFIER};
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac, NewMac};
+use reqwest::{Method, Url};
+use rust_decimal::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::env::var;
+
+pub type AccountResult<T> = Result<T, String>;
+
+// Net size of a futures position, positive for long and negative for short.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Holding {
+    pub coin: Coin,
+    pub size: Decimal,
+}
+
+#[async_trait]
+pub trait Account {
+    async fn balance(&self) -> AccountResult<Decimal>;
+
+    async fn holdings(&self) -> AccountResult<Vec<Holding>>;
+
+    // Reduces the holding of the given coin by the given signed size.
+    async fn flatten(&self, holding: Holding) -> AccountResult<()>;
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    success: bool,
+    result: Option<T>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountInfo {
+    total_account_value: Decimal,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FuturesPosition {
+    future: String,
+    net_size: Decimal,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Order {
+    avg_fill_price: Option<Decimal>,
+}
+
+// Account and order endpoints of the exchange, signed with the API key.
+#[derive(Clone)]
+pub struct Exchange {
+    client: reqwest::Client,
+    endpoint: String,
+    key: String,
+    secret: String,
+    subaccount: Option<String>,
+    fee: Decimal,
+}
+
+impl Exchange {
+    pub fn new(endpoint: &str, key: &str, secret: &str, subaccount: Option<&str>) -> Self {
+        Exchange {
+            client: reqwest::Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            key: key.to_string(),
+            secret: secret.to_string(),
+            subaccount: subaccount.map(str::to_string),
+            fee: Decimal::zero(),
+        }
+    }
+
+    // Uses the same credentials as the market data client.
+    pub fn from_env() -> Self {
+        dotenv::dotenv().ok();
+        let subaccount = var("SUBACCOUNT").expect("SUBACCOUNT is not set.");
+        let key = var("API_KEY").expect("API_KEY is not set.");
+        let secret = var("API_SECRET").expect("API_SECRET is not set.");
+        Exchange::new("https://ftx.com/api", &key, &secret, Some(&subaccount))
+    }
+
+    // Orders don't report their fees, so they are estimated with the given relative fee.
+    pub fn with_fee(mut self, fee: Decimal) -> Self {
+        self.fee = fee;
+        self
+    }
+
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> AccountResult<T> {
+        let url =
+            Url::parse(&format!("{}{}", self.endpoint, path)).map_err(|err| err.to_string())?;
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let time = Utc::now().timestamp_millis().to_string();
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .map_err(|err| err.to_string())?;
+        mac.update(format!("{}{}{}{}", time, method, url.path(), body).as_bytes());
+        let signature = hex::encode(mac.finalize().into_bytes());
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("FTX-KEY", &self.key)
+            .header("FTX-TS", time)
+            .header("FTX-SIGN", signature);
+        if let Some(subaccount) = &self.subaccount {
+            request = request.header("FTX-SUBACCOUNT", subaccount);
+        }
+        if !body.is_empty() {
+            request = request
+                .header("Content-Type", "application/json")
+                .body(body);
+        }
+
+        let response: Response<T> = request
+            .send()
+            .await
+            .map_err(|err| err.to_string())?
+            .json()
+            .await
+            .map_err(|err| err.to_string())?;
+        match response.result {
+            Some(result) if response.success => Ok(result),
+            _ => Err(response
+                .error
+                .unwrap_or_else(|| "request failed".to_string())),
+        }
+    }
+
+    async fn order(
+        &self,
+        coin: Coin,
+        side: Side,
+        size: Decimal,
+        reduce_only: bool,
+    ) -> AccountResult<Order> {
+        let side = match side {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        };
+        let body = json!({
+            "market": coin.to_string(),
+            "side": side,
+            "price": null,
+            "type": "market",
+            "size": size.to_f64(),
+            "reduceOnly": reduce_only,
+        });
+        self.request(Method::POST, "/orders", Some(body)).await
+    }
+}
+
+#[async_trait]
+impl Account for Exchange {
+    async fn balance(&self) -> AccountResult<Decimal> {
+        self.request::<AccountInfo>(Method::GET, "/account", None)
+            .await
+            .map(|account| account.total_account_value)
+    }
+
+    async fn holdings(&self) -> AccountResult<Vec<Holding>> {
+        let positions: Vec<FuturesPosition> = self.request(Method::GET, "/positions", None).await?;
+
+        let mut holdings = Vec::new();
+        for position in positions {
+            if position.net_size.is_zero() {
+                continue;
+            }
+            match position.future.parse() {
+                Ok(coin) => holdings.push(Holding {
+                    coin,
+                    size: position.net_size,
+                }),
+                Err(_) => log::warn!("Ignoring position in unknown future {}.", position.future),
+            }
+        }
+        Ok(holdings)
+    }
+
+    async fn flatten(&self, holding: Holding) -> AccountResult<()> {
+        let side = if holding.size > Decimal::zero() {
+            Side::Sell
+        } else {
+            Side::Buy
+        };
+        self.order(holding.coin, side, holding.size.abs(), true)
+            .await
+            .map(|_| ())
+    }
+}
+
+// Places market orders, as used for live trading.
+#[async_trait]
+impl Executor for Exchange {
+    async fn execute(&mut self, coin: Coin, side: Side, price: Decimal, amount: Decimal) -> Fill {
+        let fee = amount * self.fee;
+        let fill = |price| Fill { price, fee };
+        match self.order(coin, side, amount / price, false).await {
+            // Market orders may not be filled yet when they are placed.
+            Ok(order) => fill(order.avg_fill_price.unwrap_or(price)),
+            Err(err) => {
+                // Still counted as filled, the reconciler reports the missing holding.
+                log::error!("Could not place {:?} order for {}: {}", side, coin, err);
+                NOTIFIER.notify(
+                    Severity::Critical,
+                    "exchange",
+                    format!("Could not place {:?} order for {}: {}", side, coin, err),
+                );
+                fill(price)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trader::testing;
+
+    #[tokio::test]
+    async fn execute() {
+        let (exchange, orders) = testing::exchange(100, &[]).await;
+        let mut exchange = exchange.with_fee(Decimal::new(1, 3));
+        let fill = exchange
+            .execute(
+                Coin::BTC,
+                Side::Buy,
+                Decimal::new(10, 0),
+                Decimal::new(50, 0),
+            )
+            .await;
+        assert_eq!(fill.price, Decimal::new(10, 0));
+        assert_eq!(fill.fee, Decimal::new(5, 2));
+
+        let orders = orders.lock().unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0]["market"], "BTC-PERP");
+        assert_eq!(orders[0]["side"], "buy");
+        assert_eq!(orders[0]["size"], 5.0);
+        assert_eq!(orders[0]["reduceOnly"], false);
+    }
+}
@@ -1,6 +1,7 @@
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
-use std::fmt;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
 
 /*
 pub struct CoinVec<T>(Vec<Option<T>>);
@@ -28,7 +29,9 @@ impl<T> CoinVec<T> {
 }
 */
 
-#[derive(Debug, Clone, Copy, FromPrimitive, PartialEq, Eq, Hash)]
+#[derive(
+    Debug, Clone, Copy, FromPrimitive, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
 pub enum Coin {
     BTC = 0,
     ETH = 1,
@@ -57,6 +60,14 @@ impl Coin {
     pub fn all() -> Vec<Coin> {
         (0..=20).map(|i| Coin::from_usize(i).unwrap()).collect()
     }
+
+    // Coins in the order candles are kept in, without duplicates.
+    pub fn sorted(coins: &[Coin]) -> Vec<Coin> {
+        let mut coins = coins.to_vec();
+        coins.sort();
+        coins.dedup();
+        coins
+    }
 }
 
 impl fmt::Display for Coin {
@@ -65,6 +76,17 @@ impl fmt::Display for Coin {
     }
 }
 
+impl FromStr for Coin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Coin::all()
+            .into_iter()
+            .find(|coin| coin.to_string() == s || format!("{:?}", coin) == s)
+            .ok_or_else(|| format!("Unknown coin {}.", s))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -74,4 +96,11 @@ mod tests {
         assert!(Coin::from_usize(Coin::all().len() - 1).is_some());
         assert!(Coin::from_usize(Coin::all().len()).is_none());
     }
+
+    #[test]
+    fn parse() {
+        assert_eq!("BTC-PERP".parse::<Coin>(), Ok(Coin::BTC));
+        assert_eq!("ETH".parse::<Coin>(), Ok(Coin::ETH));
+        assert!("BTC-USD".parse::<Coin>().is_err());
+    }
 }
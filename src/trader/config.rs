@@ -0,0 +1,97 @@
+use super::{Costs, Grid, Limits, Params, Policy};
+use crate::notifier::Alerts;
+use serde::{Deserialize, Serialize};
+use std::{fs::read_to_string, path::Path, str::FromStr};
+
+// Number type the indicators compute with, `f64` or `Decimal` trade speed for precision.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    #[default]
+    F32,
+    F64,
+    Decimal,
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "f32" => Ok(Precision::F32),
+            "f64" => Ok(Precision::F64),
+            "decimal" => Ok(Precision::Decimal),
+            _ => Err(format!("Expected f32, f64 or decimal, got {}.", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub params: Params,
+    pub costs: Costs,
+    pub reconcile: Policy,
+    pub grid: Grid,
+    pub limits: Limits,
+    pub alerts: Alerts,
+    pub precision: Precision,
+    // Seed of the random parameter search, unless one is given.
+    pub seed: u64,
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let toml = read_to_string(path).map_err(|err| format!("{:?}: {}", path, err))?;
+        toml::from_str(&toml).map_err(|err| format!("{:?}: {}", path, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifier::Severity;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn partial() {
+        let config: Config = toml::from_str(
+            r#"
+            reconcile = "flatten"
+            precision = "decimal"
+
+            [params]
+            min_corr = 0.9
+
+            [costs]
+            fee = "0.001"
+            slippage = "0"
+
+            [alerts]
+            severity = "critical"
+            webhook = "https://example.com/hook"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.reconcile, Policy::Flatten);
+        assert_eq!(config.precision, Precision::Decimal);
+        assert_eq!(config.params.min_corr, 0.9);
+        assert_eq!(config.params.mov_period, Params::default().mov_period);
+        assert_eq!(config.costs.fee, Decimal::new(1, 3));
+        assert_eq!(config.grid, Grid::default());
+        assert_eq!(config.alerts.severity, Severity::Critical);
+        assert_eq!(config.alerts.rate_limit, Alerts::default().rate_limit);
+    }
+
+    #[test]
+    fn load() {
+        let path = std::env::temp_dir().join(format!("config-{}.toml", rand::random::<u64>()));
+        assert!(Config::load(&path).is_err());
+        std::fs::write(&path, "precision = \"f16\"").unwrap();
+        let err = Config::load(&path).unwrap_err();
+        assert!(err.contains("config-"), "{}", err);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
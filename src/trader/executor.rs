@@ -0,0 +1,98 @@
+use super::Coin;
+use async_trait::async_trait;
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    pub price: Decimal,
+    pub fee: Decimal,
+}
+
+#[async_trait]
+pub trait Executor: Send + Sync {
+    // Buys or sells the given amount in USD of a coin at about the given price.
+    async fn execute(&mut self, coin: Coin, side: Side, price: Decimal, amount: Decimal) -> Fill;
+}
+
+// Relative trading fee and slippage applied to every order, none by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Costs {
+    pub fee: Decimal,
+    pub slippage: Decimal,
+}
+
+impl Costs {
+    pub fn zero() -> Self {
+        Costs {
+            fee: Decimal::zero(),
+            slippage: Decimal::zero(),
+        }
+    }
+}
+
+// Fills every order immediately, as used for backtests and paper trading.
+pub struct Simulator {
+    costs: Costs,
+}
+
+impl Simulator {
+    pub fn new(costs: Costs) -> Self {
+        Simulator { costs }
+    }
+}
+
+#[async_trait]
+impl Executor for Simulator {
+    async fn execute(&mut self, _coin: Coin, side: Side, price: Decimal, amount: Decimal) -> Fill {
+        let price = match side {
+            Side::Buy => price * (Decimal::one() + self.costs.slippage),
+            Side::Sell => price * (Decimal::one() - self.costs.slippage),
+        };
+
+        Fill {
+            price,
+            fee: amount * self.costs.fee,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn simulator() {
+        let mut simulator = Simulator::new(Costs {
+            fee: Decimal::new(1, 2),
+            slippage: Decimal::new(1, 1),
+        });
+
+        let buy = simulator
+            .execute(
+                Coin::BTC,
+                Side::Buy,
+                Decimal::new(100, 0),
+                Decimal::new(50, 0),
+            )
+            .await;
+        assert_eq!(buy.price, Decimal::new(110, 0));
+        assert_eq!(buy.fee, Decimal::new(5, 1));
+
+        let sell = simulator
+            .execute(
+                Coin::BTC,
+                Side::Sell,
+                Decimal::new(100, 0),
+                Decimal::new(50, 0),
+            )
+            .await;
+        assert_eq!(sell.price, Decimal::new(90, 0));
+    }
+}
@@ -0,0 +1,161 @@
+use super::{Candles, Coin, Exit, Investor, Position};
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::*;
+use serde::Serialize;
+use std::{fs::File, io, path::Path};
+
+// Position as a flat record, with its profit so far.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Trade {
+    pub long: Coin,
+    pub short: Coin,
+    pub open_time: Option<DateTime<Utc>>,
+    pub close_time: Option<DateTime<Utc>>,
+    pub long_open_price: Option<Decimal>,
+    pub long_close_price: Option<Decimal>,
+    pub long_quantity: Option<Decimal>,
+    pub short_open_price: Option<Decimal>,
+    pub short_close_price: Option<Decimal>,
+    pub short_quantity: Option<Decimal>,
+    pub fees: Decimal,
+    pub exit: Option<Exit>,
+    // Realized once closed, otherwise unrealized at the last prices.
+    pub profit: Option<Decimal>,
+}
+
+impl Trade {
+    pub fn new(position: &Position, prices: Option<&Candles>) -> Self {
+        let profit = if position.is_closed() {
+            Some(position.realized_profit())
+        } else if position.is_open() {
+            prices.map(|prices| position.unrealized_profit(prices))
+        } else {
+            None
+        };
+
+        Trade {
+            long: position.long,
+            short: position.short,
+            open_time: position.open_time,
+            close_time: position.close_time,
+            long_open_price: position.long_open_price,
+            long_close_price: position.long_close_price,
+            long_quantity: position.long_quantity,
+            short_open_price: position.short_open_price,
+            short_close_price: position.short_close_price,
+            short_quantity: position.short_quantity,
+            fees: position.fees,
+            exit: position.exit,
+            profit,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Point {
+    time: DateTime<Utc>,
+    total: Decimal,
+    cash: Decimal,
+    exposure: Decimal,
+    unrealized_profit: Decimal,
+}
+
+fn write<T: Serialize, P: AsRef<Path>>(dir: P, name: &str, rows: &[T]) -> io::Result<()> {
+    let mut writer = csv::Writer::from_path(dir.as_ref().join(format!("{}.csv", name)))?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+
+    let file = File::create(dir.as_ref().join(format!("{}.json", name)))?;
+    serde_json::to_writer_pretty(file, rows).map_err(io::Error::from)
+}
+
+// Writes all positions to `trades.csv` and `trades.json`, and the equity after every candle to
+// `equity.csv` and `equity.json`.
+pub fn export<P: AsRef<Path>>(
+    dir: P,
+    investor: &Investor,
+    prices: Option<&Candles>,
+) -> io::Result<()> {
+    let trades: Vec<Trade> = investor
+        .positions()
+        .iter()
+        .map(|position| Trade::new(position, prices))
+        .collect();
+    write(&dir, "trades", &trades)?;
+
+    let equity: Vec<Point> = investor
+        .equity()
+        .iter()
+        .map(|equity| Point {
+            time: equity.time,
+            total: equity.total(),
+            cash: equity.cash,
+            exposure: equity.exposure,
+            unrealized_profit: equity.unrealized_profit,
+        })
+        .collect();
+    write(&dir, "equity", &equity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trader::testing;
+    use chrono::Duration;
+    use std::{env::temp_dir, fs::read_to_string};
+
+    #[tokio::test]
+    async fn export() {
+        let time = Utc::now();
+        let prices =
+            |btc, eth, minutes| testing::prices(btc, eth, time + Duration::minutes(minutes));
+
+        let mut investor = Investor::new();
+        investor.update(&prices(10, 10, 0));
+        investor
+            .open(
+                &prices(10, 10, 1),
+                Position::new(Coin::BTC, Coin::ETH, Decimal::new(5, 1)),
+            )
+            .await;
+        investor.close(&prices(20, 10, 2)).await;
+        investor
+            .open(
+                &prices(20, 10, 3),
+                Position::new(Coin::ETH, Coin::BTC, Decimal::new(5, 1)),
+            )
+            .await;
+        let last = prices(20, 15, 4);
+        investor.update(&last);
+
+        let dir = temp_dir().join(format!("export-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&dir).unwrap();
+        super::export(&dir, &investor, Some(&last)).unwrap();
+
+        let trades: Vec<serde_json::Value> =
+            serde_json::from_str(&read_to_string(dir.join("trades.json")).unwrap()).unwrap();
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0]["exit"], "take_profit");
+        let profit: Decimal = trades[0]["profit"].as_str().unwrap().parse().unwrap();
+        assert_eq!(profit, Decimal::new(25, 0));
+        assert!(trades[1]["close_time"].is_null());
+        assert_eq!(
+            Trade::new(&investor.positions()[1], Some(&last)).profit,
+            Some(investor.unrealized_profit(&last))
+        );
+
+        let csv = read_to_string(dir.join("trades.csv")).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("long,short,open_time,close_time"));
+        assert!(lines[2].contains(",,"));
+
+        let equity = read_to_string(dir.join("equity.csv")).unwrap();
+        assert!(equity.starts_with("time,total,cash,exposure,unrealized_profit\n"));
+        assert_eq!(equity.lines().count(), investor.equity().len() + 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
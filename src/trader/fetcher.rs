@@ -1,24 +1,32 @@
-use super::Coin;
+use super::{Coin, LazyRest, Scenario};
+use crate::{notifier::Severity, METRICS, NOTIFIER};
 use chrono::{DateTime, Duration, DurationRound, Utc};
-use ftx::rest::{Price, Rest};
+use ftx::rest::Price;
 use futures::future::join_all;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::{
+    collections::VecDeque,
+    fs::{read, File},
+    path::PathBuf,
+    time::Instant,
+};
 use tokio::time::sleep;
 
 pub type Candles = Vec<Candle>;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Candle {
+    pub coin: Coin,
     pub close: Decimal,
     pub volume: Decimal,
     pub time: DateTime<Utc>,
 }
 
-impl From<Price> for Candle {
-    fn from(price: Price) -> Self {
+impl Candle {
+    pub fn new(coin: Coin, price: Price) -> Self {
         Candle {
+            coin,
             close: price.close,
             volume: price.volume,
             time: price.start_time,
@@ -26,13 +34,54 @@ impl From<Price> for Candle {
     }
 }
 
+pub trait ByCoin {
+    fn coin(&self, coin: Coin) -> &Candle;
+}
+
+// Candles of a time are kept in coin order, so they are found by binary search.
+impl ByCoin for [Candle] {
+    fn coin(&self, coin: Coin) -> &Candle {
+        match self.binary_search_by_key(&coin, |candle| candle.coin) {
+            Ok(i) => &self[i],
+            Err(_) => panic!("No candle for coin {}.", coin),
+        }
+    }
+}
+
+// Candle of caches written before candles had a coin.
+#[derive(Deserialize)]
+struct Unnamed {
+    #[allow(dead_code)]
+    close: Decimal,
+}
+
+// Reads a cache, failing with a hint to download it again if it can't be used.
+fn read_history(bytes: &[u8]) -> Result<Vec<Candles>, String> {
+    serde_cbor::from_slice(bytes).map_err(|err| {
+        if serde_cbor::from_slice::<Vec<Vec<Unnamed>>>(bytes).is_ok() {
+            "it was written by an older version without coins, re-run fetch-data".to_string()
+        } else {
+            format!("{}, re-run fetch-data", err)
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Freshness {
+    pub coin: Coin,
+    // Time of the latest candle.
+    pub candle: Option<DateTime<Utc>>,
+    // Time of the latest successful request.
+    pub fetched: Option<DateTime<Utc>>,
+}
+
 pub struct Buf {
     coin: Coin,
     interval: Duration,
     curr: DateTime<Utc>,
     buf: VecDeque<Candle>,
     last: Option<Candle>,
-    #[allow(dead_code)]
+    fetched: Option<DateTime<Utc>>,
     realtime: bool,
 }
 
@@ -46,11 +95,12 @@ impl Buf {
             curr,
             buf: VecDeque::new(),
             last: None,
+            fetched: None,
             realtime: false,
         }
     }
 
-    pub async fn fetch(&mut self, rest: &Rest) {
+    pub async fn fetch(&mut self, rest: &LazyRest) {
         let sleep_duration = (self.curr - (Utc::now() - self.interval)).max(Duration::zero());
         log::debug!("Sleeping for {:?}.", sleep_duration);
         if sleep_duration > Duration::zero() {
@@ -63,7 +113,8 @@ impl Buf {
         let start_time = self.curr - self.interval * 5;
         let end_time = self.curr + self.interval * 4995;
 
-        let prices = rest
+        let started = Instant::now();
+        let result = rest
             .get_historical_prices(
                 &self.coin.to_string(),
                 self.interval.num_seconds() as u32,
@@ -71,17 +122,44 @@ impl Buf {
                 Some(start_time),
                 Some(end_time),
             )
-            .await
-            .unwrap();
+            .await;
+        METRICS.fetches.coin(self.coin).inc();
+        METRICS
+            .fetch_seconds
+            .coin(self.coin)
+            .add(started.elapsed().as_secs_f64());
+
+        let prices = match result {
+            Ok(prices) => {
+                self.fetched = Some(Utc::now());
+                prices
+            }
+            Err(err) => {
+                // Back off and let the caller retry.
+                METRICS.fetch_errors.coin(self.coin).inc();
+                log::error!("Failed to fetch prices for coin {}: {:?}", self.coin, err);
+                NOTIFIER.notify(
+                    Severity::Warning,
+                    "exchange",
+                    format!("Failed to fetch prices for coin {}: {:?}", self.coin, err),
+                );
+                sleep(self.interval.to_std().unwrap()).await;
+                return;
+            }
+        };
 
         if prices.len() == 0 {
             log::warn!("No data for coin {}.", self.coin);
+            NOTIFIER.notify(
+                Severity::Warning,
+                &format!("gap {}", self.coin),
+                format!("No data for coin {} from {}.", self.coin, self.curr),
+            );
             while self.curr < end_time {
                 let last = self.last.unwrap();
                 self.buf.push_back(Candle {
-                    close: last.close,
-                    volume: last.volume,
                     time: self.curr,
+                    ..last
                 });
                 self.curr = self.curr + self.interval;
             }
@@ -91,7 +169,7 @@ impl Buf {
                     continue;
                 } else if price.start_time == self.curr {
                     self.curr = price.start_time + self.interval;
-                    self.buf.push_back(price.into());
+                    self.buf.push_back(Candle::new(self.coin, price));
                 } else {
                     // Backfill until next data.
                     log::warn!(
@@ -100,12 +178,19 @@ impl Buf {
                         price.start_time,
                         self.coin
                     );
+                    NOTIFIER.notify(
+                        Severity::Warning,
+                        &format!("gap {}", self.coin),
+                        format!(
+                            "Missing data for coin {} from {} to {}.",
+                            self.coin, self.curr, price.start_time
+                        ),
+                    );
                     while price.start_time > self.curr {
                         let last = self.last.unwrap();
                         self.buf.push_back(Candle {
-                            close: last.close,
-                            volume: last.volume,
                             time: self.curr,
+                            ..last
                         });
                         self.curr = self.curr + self.interval;
                     }
@@ -113,7 +198,7 @@ impl Buf {
                     // Insert next known data.
                     assert_eq!(price.start_time, self.curr);
                     self.curr = price.start_time + self.interval;
-                    self.buf.push_back(price.into());
+                    self.buf.push_back(Candle::new(self.coin, price));
                 }
                 if let Some(&candle) = self.buf.back() {
                     self.last = Some(candle);
@@ -122,7 +207,7 @@ impl Buf {
         }
     }
 
-    pub async fn next(&mut self, rest: &Rest) -> Option<Candle> {
+    pub async fn next(&mut self, rest: &LazyRest) -> Option<Candle> {
         if let Some(price) = self.buf.pop_front() {
             Some(price)
         } else {
@@ -139,35 +224,96 @@ impl Buf {
     pub fn is_realtime(&self) -> bool {
         self.realtime
     }
+
+    pub fn freshness(&self) -> Freshness {
+        Freshness {
+            coin: self.coin,
+            candle: self.last.map(|candle| candle.time),
+            fetched: self.fetched,
+        }
+    }
 }
 
 pub struct Fetcher {
     bufs: Vec<Buf>,
-    #[cfg(feature = "backtest")]
+    interval: Duration,
+    from: DateTime<Utc>,
+    to: Option<DateTime<Utc>>,
+    cache: Option<PathBuf>,
     buf: Vec<Candles>,
-    #[cfg(feature = "backtest")]
     fetched: bool,
 }
 
 impl Fetcher {
     pub fn new(coins: &[Coin], from: DateTime<Utc>, interval: Duration) -> Self {
-        let curr = from.duration_round(interval).unwrap();
-
         let mut bufs = Vec::new();
-        for &coin in coins {
+        for coin in Coin::sorted(coins) {
             bufs.push(Buf::new(coin, from, interval))
         }
 
         Fetcher {
             bufs,
-            #[cfg(feature = "backtest")]
+            interval,
+            from: from.duration_round(interval).unwrap(),
+            to: None,
+            cache: None,
             buf: Vec::new(),
-            #[cfg(feature = "backtest")]
             fetched: false,
         }
     }
 
-    async fn fetch(&mut self, rest: &Rest) -> Option<Candles> {
+    // Keeps the candles in the given file. Unless they are read from there with `read_cache`,
+    // they are fetched from the API and overwrite it.
+    pub fn cached<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.cache = Some(path.into());
+        self
+    }
+
+    // Reads the candles from the cache right away, so an unusable cache fails before the run.
+    // An empty or missing cache is still filled from the API on the first candles.
+    pub fn read_cache(mut self) -> Result<Self, String> {
+        let path = self.cache.clone().expect("No cache file set.");
+
+        match read(&path) {
+            Ok(bytes) if !bytes.is_empty() => {
+                log::info!("Loading backtest data from {:?}.", path);
+                let history = read_history(&bytes)
+                    .map_err(|err| format!("could not read {:?}: {}", path, err))?;
+                self.set_history(history);
+                log::info!("Done loading backtest data!");
+            }
+            _ => (),
+        }
+        Ok(self)
+    }
+
+    // Generates all candles from the given scenario instead of fetching them.
+    pub fn synthetic(mut self, scenario: &Scenario) -> Result<Self, String> {
+        let coins: Vec<Coin> = self.bufs.iter().map(|buf| buf.coin).collect();
+        scenario.check(&coins)?;
+        if let Some(interval) = self.bufs.first().map(|buf| buf.interval) {
+            let history = scenario.generate(self.from, interval)?;
+            self.set_history(history);
+        }
+        Ok(self)
+    }
+
+    pub fn until(mut self, to: DateTime<Utc>) -> Self {
+        self.to = Some(to);
+        self
+    }
+
+    // Starts with the candle after the given time instead, to only fetch what was missed since.
+    pub fn resume(&mut self, after: DateTime<Utc>) {
+        for buf in &mut self.bufs {
+            *buf = Buf::new(buf.coin, after + buf.interval, buf.interval);
+        }
+        if let Some(buf) = self.bufs.first() {
+            self.from = buf.curr;
+        }
+    }
+
+    async fn fetch(&mut self, rest: &LazyRest) -> Option<Candles> {
         let mut vec: Candles = Vec::new();
 
         let mut futures = Vec::new();
@@ -190,76 +336,88 @@ impl Fetcher {
         Some(vec)
     }
 
-    #[cfg(not(feature = "backtest"))]
-    pub async fn next(&mut self, rest: &Rest) -> Option<Candles> {
-        self.fetch(rest).await
+    fn in_range(&self, prices: &Candles) -> bool {
+        self.from <= prices[0].time && self.to.map(|to| prices[0].time < to).unwrap_or(true)
     }
 
-    // TODO: Make this non-blocking.
-    #[cfg(feature = "backtest")]
-    async fn fetch_and_cache(&mut self, rest: &Rest) {
-        use std::time::SystemTime;
-        use std::{
-            fs::OpenOptions,
-            io::{Read, Write},
+    pub async fn next(&mut self, rest: &LazyRest) -> Option<Candles> {
+        let next = if self.cache.is_some() || self.fetched {
+            if !self.fetched {
+                self.refresh(rest).await;
+            }
+            self.buf.pop()
+        } else {
+            self.fetch(rest).await
         };
 
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open("cache.bin")
-            .unwrap();
-
-        let metadata = file.metadata().unwrap();
-
-        if metadata.len() > 0
-        /*&& SystemTime::now()
-        .duration_since(metadata.modified().unwrap())
-        .unwrap()
-        .as_secs()
-        < 3600 * 8*/
-        {
-            // Load data from cache.
-            log::info!("Loading backtest data from cache.");
-
-            //let mut bin = Vec::new();
-            //file.read_to_end(&mut bin).await.unwrap();
-            //self.buf = bincode::deserialize(&bin[..]).unwrap();
-            self.buf = serde_cbor::from_reader(file).unwrap();
-        } else {
-            // Fetch data and save to cache.
-            log::info!("Loading backtest data from API.");
+        next.filter(|prices| self.in_range(prices))
+    }
 
-            while let Some(prices) = self.fetch(rest).await {
-                self.buf.push(prices);
-                if self.is_realtime() {
-                    break;
-                }
+    // Fetches all candles until the end of the range or until real time is reached.
+    pub async fn download(&mut self, rest: &LazyRest) -> Vec<Candles> {
+        let mut history = Vec::new();
+        while let Some(prices) = self.fetch(rest).await {
+            if !self.in_range(&prices) {
+                break;
             }
-            self.buf.reverse();
+            history.push(prices);
+            if self.is_realtime() {
+                break;
+            }
+        }
+        history
+    }
 
-            log::info!("Saving backtest data to file.");
+    // Fetches candles from the API and overwrites the cache.
+    pub async fn refresh(&mut self, rest: &LazyRest) {
+        let path = self.cache.clone().expect("No cache file set.");
 
-            //let bin = bincode::serialize(&self.buf).unwrap();
-            //file.write_all(&bin[..]).await.unwrap();
-            serde_cbor::to_writer(file, &self.buf).unwrap();
-        }
+        log::info!("Loading backtest data from API.");
+        let history = self.download(rest).await;
+
+        log::info!("Saving backtest data to {:?}.", path);
+        serde_cbor::to_writer(File::create(&path).unwrap(), &history).unwrap();
 
+        self.buf = history;
+        self.buf.reverse();
         self.fetched = true;
-        log::info!("Done loading backtest data!");
     }
 
-    #[cfg(feature = "backtest")]
-    pub async fn next(&mut self, rest: &Rest) -> Option<Candles> {
-        if !self.fetched {
-            self.fetch_and_cache(rest).await;
+    // Buffers the candles in range, in the order of the coins of this fetcher.
+    fn set_history(&mut self, history: Vec<Candles>) {
+        self.buf = history
+            .into_iter()
+            .filter(|prices| self.in_range(prices))
+            .map(|mut prices| {
+                prices.sort_by_key(|candle| candle.coin);
+                self.bufs.iter().map(|buf| *prices.coin(buf.coin)).collect()
+            })
+            .collect();
+        self.buf.reverse();
+        self.fetched = true;
+    }
+
+    // Returns all remaining candles in chronological order.
+    pub async fn history(&mut self, rest: &LazyRest) -> Vec<Candles> {
+        if self.cache.is_some() || self.fetched {
+            if !self.fetched {
+                self.refresh(rest).await;
+            }
+            self.buf.drain(..).rev().collect()
+        } else {
+            self.download(rest).await
         }
+    }
 
-        self.buf.pop()
+    pub fn interval(&self) -> Duration {
+        self.interval
     }
 
-    fn is_realtime(&self) -> bool {
+    pub fn freshness(&self) -> Vec<Freshness> {
+        self.bufs.iter().map(Buf::freshness).collect()
+    }
+
+    pub fn is_realtime(&self) -> bool {
         self.bufs.iter().fold(false, |a, b| a || b.is_realtime())
     }
 }
@@ -267,16 +425,43 @@ impl Fetcher {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::trader::lazy_rest;
     use chrono::TimeZone;
-    use std::env::var;
+
+    #[test]
+    fn cache() {
+        let candle = |coin| Candle {
+            coin,
+            close: Decimal::new(1, 0),
+            volume: Decimal::new(2, 0),
+            time: Utc.ymd(2021, 1, 1).and_hms(0, 0, 0),
+        };
+        let history = vec![vec![candle(Coin::BTC), candle(Coin::SOL)]];
+        let bytes = serde_cbor::to_vec(&history).unwrap();
+        assert_eq!(read_history(&bytes).unwrap(), history);
+        assert_eq!(history[0].coin(Coin::SOL).coin, Coin::SOL);
+
+        #[derive(Serialize)]
+        struct Old {
+            close: Decimal,
+            volume: Decimal,
+            time: DateTime<Utc>,
+        }
+        let old = vec![vec![Old {
+            close: Decimal::new(1, 0),
+            volume: Decimal::new(2, 0),
+            time: Utc::now(),
+        }]];
+        let err = read_history(&serde_cbor::to_vec(&old).unwrap()).unwrap_err();
+        assert!(err.contains("older version"));
+        assert!(read_history(&[0xff])
+            .unwrap_err()
+            .contains("re-run fetch-data"));
+    }
 
     #[tokio::test]
     async fn test_start_time() {
-        dotenv::dotenv().ok();
-        let subaccount = Some(var("SUBACCOUNT").unwrap());
-        let key = var("API_KEY").unwrap();
-        let secret = var("API_SECRET").unwrap();
-        let rest = Rest::new(key.clone(), secret.clone(), subaccount.clone());
+        let rest = lazy_rest();
 
         let from = Utc.ymd(2021, 1, 1).and_hms(0, 0, 0);
         let mut buf = Buf::new(Coin::BTC, from, Duration::seconds(15));
@@ -286,11 +471,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_multiple() {
-        dotenv::dotenv().ok();
-        let subaccount = Some(var("SUBACCOUNT").unwrap());
-        let key = var("API_KEY").unwrap();
-        let secret = var("API_SECRET").unwrap();
-        let rest = Rest::new(key.clone(), secret.clone(), subaccount.clone());
+        let rest = lazy_rest();
 
         let from = Utc.ymd(2021, 1, 1).and_hms(0, 0, 0);
         let interval = Duration::seconds(15);
@@ -308,11 +489,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_current() {
-        dotenv::dotenv().ok();
-        let subaccount = Some(var("SUBACCOUNT").unwrap());
-        let key = var("API_KEY").unwrap();
-        let secret = var("API_SECRET").unwrap();
-        let rest = Rest::new(key.clone(), secret.clone(), subaccount.clone());
+        let rest = lazy_rest();
 
         let from = Utc::now();
         let interval = Duration::seconds(15);
@@ -323,11 +500,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_fetcher() {
-        dotenv::dotenv().ok();
-        let subaccount = Some(var("SUBACCOUNT").unwrap());
-        let key = var("API_KEY").unwrap();
-        let secret = var("API_SECRET").unwrap();
-        let rest = Rest::new(key.clone(), secret.clone(), subaccount.clone());
+        let rest = lazy_rest();
 
         let from = Utc.ymd(2021, 1, 1).and_hms(0, 0, 0);
         let interval = Duration::seconds(15);
@@ -0,0 +1,423 @@
+use super::{Candle, Candles, Coin};
+use chrono::{DateTime, Duration, DurationRound, NaiveDateTime, TimeZone, Utc};
+use parquet::{
+    file::reader::{FileReader, SerializedFileReader},
+    record::Field,
+};
+use rust_decimal::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    path::Path,
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeFormat {
+    Rfc3339,
+    // Seconds or milliseconds since the epoch.
+    Unix,
+    UnixMillis,
+    // As for `NaiveDateTime::parse_from_str`, in UTC.
+    Custom(String),
+}
+
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "rfc3339" => TimeFormat::Rfc3339,
+            "unix" => TimeFormat::Unix,
+            "unix_ms" => TimeFormat::UnixMillis,
+            _ => TimeFormat::Custom(s.to_string()),
+        })
+    }
+}
+
+impl TimeFormat {
+    pub fn parse(&self, s: &str) -> Option<DateTime<Utc>> {
+        let s = s.trim();
+        match self {
+            TimeFormat::Rfc3339 => s.parse().ok(),
+            TimeFormat::Unix => millis(s.parse::<f64>().ok()? * 1000.0),
+            TimeFormat::UnixMillis => millis(s.parse().ok()?),
+            TimeFormat::Custom(format) => NaiveDateTime::parse_from_str(s, format)
+                .ok()
+                .map(|time| Utc.from_utc_datetime(&time)),
+        }
+    }
+}
+
+fn millis(ms: f64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_millis_opt(ms.round() as i64).single()
+}
+
+fn decimal(s: &str) -> Option<Decimal> {
+    let s = s.trim();
+    Decimal::from_str(s)
+        .or_else(|_| Decimal::from_scientific(s))
+        .ok()
+}
+
+// Names of the columns to read candles from. Without a coin column, each file holds one coin
+// and is named after it, like `BTC.csv`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Columns {
+    pub time: String,
+    pub close: String,
+    pub volume: String,
+    pub coin: Option<String>,
+    pub format: TimeFormat,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns {
+            time: "time".to_string(),
+            close: "close".to_string(),
+            volume: "volume".to_string(),
+            coin: None,
+            format: TimeFormat::Rfc3339,
+        }
+    }
+}
+
+impl Columns {
+    fn file_coin(&self, path: &Path) -> Result<Option<Coin>, String> {
+        if self.coin.is_some() {
+            return Ok(None);
+        }
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("");
+        stem.parse()
+            .map(Some)
+            .map_err(|err| format!("{} Name the file after its coin.", err))
+    }
+
+    // Skips candles of symbols that aren't a coin in a coin column.
+    fn candle(
+        &self,
+        get: impl Fn(&str) -> Option<String>,
+        coin: Option<Coin>,
+    ) -> Result<Option<Candle>, String> {
+        let column = |name: &str| get(name).ok_or_else(|| format!("no column {}", name));
+        let coin = match coin {
+            Some(coin) => coin,
+            None => match column(self.coin.as_ref().unwrap())?.parse() {
+                Ok(coin) => coin,
+                Err(_) => return Ok(None),
+            },
+        };
+        let time = column(&self.time)?;
+        let close = column(&self.close)?;
+        let volume = column(&self.volume)?;
+        Ok(Some(Candle {
+            coin,
+            close: decimal(&close).ok_or_else(|| format!("invalid close {}", close))?,
+            volume: decimal(&volume).ok_or_else(|| format!("invalid volume {}", volume))?,
+            time: self
+                .format
+                .parse(&time)
+                .ok_or_else(|| format!("invalid time {} for {:?}", time, self.format))?,
+        }))
+    }
+
+    pub fn read_csv<P: AsRef<Path>>(&self, path: P) -> Result<Vec<Candle>, String> {
+        let coin = self.file_coin(path.as_ref())?;
+        let mut reader = csv::Reader::from_path(path).map_err(|err| err.to_string())?;
+        let headers = reader.headers().map_err(|err| err.to_string())?.clone();
+        let mut candles = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(|err| err.to_string())?;
+            let get = |name: &str| {
+                let i = headers.iter().position(|header| header.trim() == name)?;
+                record.get(i).map(str::to_string)
+            };
+            candles.extend(self.candle(get, coin)?);
+        }
+        Ok(candles)
+    }
+
+    pub fn read_parquet<P: AsRef<Path>>(&self, path: P) -> Result<Vec<Candle>, String> {
+        let coin = self.file_coin(path.as_ref())?;
+        let file = File::open(path).map_err(|err| err.to_string())?;
+        let reader = SerializedFileReader::new(file).map_err(|err| err.to_string())?;
+        let mut candles = Vec::new();
+        for row in reader.get_row_iter(None).map_err(|err| err.to_string())? {
+            let row = row.map_err(|err| err.to_string())?;
+            let get = |name: &str| {
+                let (_, field) = row.get_column_iter().find(|(column, _)| *column == name)?;
+                Some(match field {
+                    Field::Str(s) => s.clone(),
+                    // Typed timestamps are read regardless of the time format.
+                    Field::TimestampMillis(ms) => self.typed(*ms),
+                    Field::TimestampMicros(us) => self.typed(us / 1000),
+                    field => field.to_string(),
+                })
+            };
+            candles.extend(self.candle(get, coin)?);
+        }
+        Ok(candles)
+    }
+
+    fn typed(&self, ms: i64) -> String {
+        // Out of range timestamps are left for the time format to reject.
+        let time = match Utc.timestamp_millis_opt(ms).single() {
+            Some(time) => time,
+            None => return ms.to_string(),
+        };
+        match &self.format {
+            TimeFormat::Custom(format) => time.format(format).to_string(),
+            TimeFormat::Unix => time.timestamp().to_string(),
+            TimeFormat::UnixMillis => ms.to_string(),
+            TimeFormat::Rfc3339 => time.to_rfc3339(),
+        }
+    }
+
+    // Reads Parquet files by their extension and all others as CSV, failing with the file name.
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<Candle>, String> {
+        let path = path.as_ref();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("parquet") => self.read_parquet(path),
+            _ => self.read_csv(path),
+        }
+        .map_err(|err| format!("{:?}: {}", path, err))
+    }
+}
+
+// Aligns candles to the interval, keeping the last close and summing the volume within each
+// interval. Each coin starts with its first candle, so only the time range all coins have data
+// for is kept. Missing candles are filled with the previous one, as the fetcher does.
+pub fn align(
+    candles: Vec<Candle>,
+    interval: Duration,
+    from: DateTime<Utc>,
+    to: Option<DateTime<Utc>>,
+) -> Vec<Candles> {
+    let mut coins: HashMap<Coin, BTreeMap<DateTime<Utc>, Candle>> = HashMap::new();
+    let mut candles = candles;
+    candles.sort_by_key(|candle| candle.time);
+    for candle in candles {
+        let time = candle.time.duration_trunc(interval).unwrap();
+        coins
+            .entry(candle.coin)
+            .or_default()
+            .entry(time)
+            .and_modify(|aligned| {
+                aligned.close = candle.close;
+                aligned.volume += candle.volume;
+            })
+            .or_insert(Candle { time, ..candle });
+    }
+
+    let mut order: Vec<Coin> = coins.keys().copied().collect();
+    order.sort_by_key(|&coin| coin as usize);
+    let start = coins
+        .values()
+        .filter_map(|candles| candles.keys().next())
+        .max()
+        .map_or(from, |&start| {
+            start.max(from.duration_round(interval).unwrap())
+        });
+    let end = coins
+        .values()
+        .filter_map(|candles| candles.keys().next_back())
+        .min();
+
+    let mut history = Vec::new();
+    let mut time = start;
+    while end.is_some_and(|&end| time <= end) && to.is_none_or(|to| time < to) {
+        let prices = order
+            .iter()
+            .map(|coin| {
+                let (_, &last) = coins[coin].range(..=time).next_back().unwrap();
+                Candle { time, ..last }
+            })
+            .collect();
+        history.push(prices);
+        time += interval;
+    }
+    history
+}
+
+// Reads and aligns the candles of the given coins, or of all coins in the files if none are
+// given, then overwrites the cache. Fails without touching the cache if a file can't be read or
+// the files have no candles for some of the given coins.
+pub fn import<P: AsRef<Path>>(
+    paths: &[P],
+    columns: &Columns,
+    coins: &[Coin],
+    interval: Duration,
+    (from, to): (DateTime<Utc>, Option<DateTime<Utc>>),
+    cache: &Path,
+) -> Result<Vec<Candles>, String> {
+    let mut candles = Vec::new();
+    for path in paths {
+        log::info!("Importing candles from {:?}.", path.as_ref());
+        candles.extend(
+            columns
+                .read(path)?
+                .into_iter()
+                .filter(|candle| coins.is_empty() || coins.contains(&candle.coin)),
+        );
+    }
+    let missing: Vec<String> = coins
+        .iter()
+        .filter(|&&coin| candles.iter().all(|candle| candle.coin != coin))
+        .map(Coin::to_string)
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("no candles for {}", missing.join(", ")));
+    }
+    let history = align(candles, interval, from, to);
+
+    log::info!("Saving {} candles to {:?}.", history.len(), cache);
+    let file = File::create(cache).map_err(|err| format!("{:?}: {}", cache, err))?;
+    serde_cbor::to_writer(file, &history).map_err(|err| format!("{:?}: {}", cache, err))?;
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::{
+        data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type},
+        file::{properties::WriterProperties, writer::SerializedFileWriter},
+        schema::parser::parse_message_type,
+    };
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, write},
+        sync::Arc,
+    };
+
+    #[test]
+    fn csv() {
+        let path = temp_dir().join(format!("import-{}.csv", rand::random::<u64>()));
+        write(
+            &path,
+            "date,symbol,price,qty\n\
+             2021-01-01 00:00:05,BTC-PERP,100,1\n\
+             2021-01-01 00:00:10,BTC-PERP,101,2\n\
+             2021-01-01 00:00:30,BTC-PERP,1.02e2,1\n\
+             2021-01-01 00:00:00,ETH,10,5\n\
+             2021-01-01 00:00:45,ETH,11,5\n\
+             2021-01-01 00:00:15,XYZ,1,1\n",
+        )
+        .unwrap();
+        let columns = Columns {
+            time: "date".to_string(),
+            close: "price".to_string(),
+            volume: "qty".to_string(),
+            coin: Some("symbol".to_string()),
+            format: "%Y-%m-%d %H:%M:%S".parse().unwrap(),
+        };
+        let candles = columns.read(&path).unwrap();
+        assert_eq!(candles.len(), 5);
+        // Without a coin column, the file has to be named after its coin.
+        let err = Columns::default().read(&path).unwrap_err();
+        assert!(err.contains("Unknown coin import-"), "{}", err);
+
+        let from = Utc.ymd(2021, 1, 1).and_hms(0, 0, 0);
+        let history = align(candles, Duration::seconds(15), from, None);
+        let prices: Vec<Vec<(Coin, Decimal, Decimal)>> = history
+            .iter()
+            .map(|prices| {
+                assert!(prices.iter().all(|candle| candle.time == prices[0].time));
+                prices
+                    .iter()
+                    .map(|candle| (candle.coin, candle.close, candle.volume))
+                    .collect()
+            })
+            .collect();
+        let d = |n| Decimal::new(n, 0);
+        assert_eq!(history[0][0].time, from);
+        assert_eq!(
+            prices,
+            vec![
+                vec![(Coin::BTC, d(101), d(3)), (Coin::ETH, d(10), d(5))],
+                vec![(Coin::BTC, d(101), d(3)), (Coin::ETH, d(10), d(5))],
+                vec![(Coin::BTC, d(102), d(1)), (Coin::ETH, d(10), d(5))],
+            ]
+        );
+
+        let cache = temp_dir().join(format!("import-{}.bin", rand::random::<u64>()));
+        let range = (from, None);
+        let interval = Duration::seconds(15);
+        let err = import(
+            &[&path],
+            &columns,
+            &[Coin::BTC, Coin::SOL],
+            interval,
+            range,
+            &cache,
+        );
+        assert_eq!(err.unwrap_err(), "no candles for SOL-PERP");
+        assert!(!cache.exists());
+        let history = import(&[&path], &columns, &[Coin::BTC], interval, range, &cache).unwrap();
+        assert_eq!(history.len(), 3);
+        assert!(cache.exists());
+        // All coins in the file by default.
+        let history = import(&[&path], &columns, &[], interval, range, &cache).unwrap();
+        assert_eq!(history[0].len(), 2);
+    }
+
+    #[test]
+    fn parquet() {
+        // Named after its coin, so in a directory of its own.
+        let dir = temp_dir().join(format!("import-{}", rand::random::<u64>()));
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("SOL.parquet");
+        let schema = Arc::new(
+            parse_message_type(
+                "message candles {
+                    required int64 time;
+                    required double close;
+                    required binary volume (UTF8);
+                }",
+            )
+            .unwrap(),
+        );
+        let mut writer = SerializedFileWriter::new(
+            File::create(&path).unwrap(),
+            schema,
+            Arc::new(WriterProperties::builder().build()),
+        )
+        .unwrap();
+        let mut group = writer.next_row_group().unwrap();
+        let mut column = group.next_column().unwrap().unwrap();
+        column
+            .typed::<Int64Type>()
+            .write_batch(&[1609459200, 1609459215], None, None)
+            .unwrap();
+        column.close().unwrap();
+        let mut column = group.next_column().unwrap().unwrap();
+        column
+            .typed::<DoubleType>()
+            .write_batch(&[1.5, 2.25], None, None)
+            .unwrap();
+        column.close().unwrap();
+        let mut column = group.next_column().unwrap().unwrap();
+        column
+            .typed::<ByteArrayType>()
+            .write_batch(&[ByteArray::from("7"), ByteArray::from("8")], None, None)
+            .unwrap();
+        column.close().unwrap();
+        group.close().unwrap();
+        writer.close().unwrap();
+
+        let columns = Columns {
+            format: TimeFormat::Unix,
+            ..Columns::default()
+        };
+        let candles = columns.read(&path).unwrap();
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[0].coin, Coin::SOL);
+        assert_eq!(candles[0].time, Utc.ymd(2021, 1, 1).and_hms(0, 0, 0));
+        assert_eq!(candles[1].close, Decimal::new(225, 2));
+        assert_eq!(candles[1].volume, Decimal::new(8, 0));
+    }
+}
@@ -1,11 +1,62 @@
-use std::fmt::Pointer;
-
-use super::{Candles, Position, Wallet};
+use super::{
+    ByCoin, Candles, Coin, Costs, Equity, Executor, Exit, Holding, Position, Side, Simulator,
+    Wallet,
+};
+use crate::{
+    journal::{Event, Rejection},
+    notifier::Severity,
+    JOURNAL, NOTIFIER,
+};
 use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+fn default_executor() -> Box<dyn Executor> {
+    Box::new(Simulator::new(Costs::zero()))
+}
+
+// Checked before opening new positions, unlimited by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    // Maximum number of open positions.
+    pub max_positions: Option<usize>,
+    // Total realized loss after which no more positions are opened.
+    pub max_loss: Option<Decimal>,
+}
+
+// Holding taken over from the exchange, valued against the price it was adopted at.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Adopted {
+    pub coin: Coin,
+    pub size: Decimal,
+    pub price: Decimal,
+}
 
+impl Adopted {
+    fn unrealized_profit(&self, prices: &Candles) -> Decimal {
+        prices
+            .iter()
+            .find(|candle| candle.coin == self.coin)
+            .map(|candle| self.size * (candle.close - self.price))
+            .unwrap_or_else(Decimal::zero)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Investor {
     wallet: Wallet,
     positions: Vec<Position>,
+    adopted: Vec<Adopted>,
+    #[serde(default)]
+    limits: Limits,
+    #[serde(default)]
+    paused: bool,
+    #[serde(skip, default = "default_executor")]
+    executor: Box<dyn Executor>,
+    // Counts changes to the saved state, so it is only saved again when it changed.
+    #[serde(skip)]
+    changes: usize,
 }
 
 impl Investor {
@@ -13,34 +64,372 @@ impl Investor {
         Investor {
             wallet: Wallet::new(Decimal::new(100, 0), 2),
             positions: Vec::new(),
+            adopted: Vec::new(),
+            limits: Limits::default(),
+            paused: false,
+            executor: default_executor(),
+            changes: 0,
         }
     }
 
+    pub fn with_executor(mut self, executor: Box<dyn Executor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+        self.changes += 1;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Stops or resumes opening new positions, open positions are still closed.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.changes += 1;
+    }
+
+    fn exceeds_limits(&self) -> bool {
+        let open = self
+            .positions
+            .iter()
+            .filter(|p| p.is_open() && !p.is_closed())
+            .count();
+        let max_positions = self.limits.max_positions.map_or(false, |max| open >= max);
+        let max_loss = self
+            .limits
+            .max_loss
+            .map_or(false, |max| self.total_realized_profit() <= -max);
+        max_positions || max_loss
+    }
+
     pub async fn open(&mut self, prices: &Candles, mut position: Position) {
+        if self.paused {
+            self.reject(prices, &position, Rejection::Paused);
+            return;
+        }
+        if self.exceeds_limits() {
+            self.reject(prices, &position, Rejection::RiskLimit);
+            return;
+        }
+
         let already_invested = self
             .positions
             .iter()
             .filter(|p| p.long == position.long && p.short == position.short)
             .count()
             > 0;
-        if !already_invested {
-            if let Some(borrowed) = self.wallet.borrow() {
-                position.open(prices, borrowed);
-                self.positions.push(position);
-            }
+        if already_invested {
+            self.reject(prices, &position, Rejection::AlreadyInvested);
+            return;
+        }
+
+        if let Some(borrowed) = self.wallet.borrow() {
+            let half = borrowed / Decimal::new(2, 0);
+            let long = self
+                .executor
+                .execute(
+                    position.long,
+                    Side::Buy,
+                    prices.coin(position.long).close,
+                    half,
+                )
+                .await;
+            let short = self
+                .executor
+                .execute(
+                    position.short,
+                    Side::Sell,
+                    prices.coin(position.short).close,
+                    half,
+                )
+                .await;
+            position.open_filled(prices, borrowed, long, short);
+            NOTIFIER.notify(
+                Severity::Info,
+                &format!("open {}/{}", position.long, position.short),
+                format!(
+                    "Opened {}/{} with {:.2}.",
+                    position.long, position.short, borrowed
+                ),
+            );
+            self.positions.push(position);
+            self.changes += 1;
+        } else {
+            self.reject(prices, &position, Rejection::NoFunds);
         }
     }
 
+    fn reject(&self, prices: &Candles, position: &Position, reason: Rejection) {
+        log::debug!(
+            "Rejected {}/{}: {:?}",
+            position.long,
+            position.short,
+            reason
+        );
+        // Only rejections by exposure or risk limits are worth an alert.
+        let message = match reason {
+            Rejection::NoFunds => Some("all wallet parts are in use"),
+            Rejection::RiskLimit => Some("risk limits are exceeded"),
+            _ => None,
+        };
+        if let Some(message) = message {
+            NOTIFIER.notify(
+                Severity::Warning,
+                "rejected",
+                format!(
+                    "Rejected {}/{}, {}.",
+                    position.long, position.short, message
+                ),
+            );
+        }
+        JOURNAL.record(Event::Rejected {
+            time: prices[0].time,
+            long: position.long,
+            short: position.short,
+            reason,
+        });
+    }
+
     pub async fn close(&mut self, prices: &Candles) {
+        for i in 0..self.positions.len() {
+            let position = &self.positions[i];
+            if !position.is_open() || position.is_closed() {
+                continue;
+            }
+            if let Some(exit) = position.should_close(prices) {
+                self.close_at(i, prices, exit).await;
+            }
+        }
+    }
+
+    // Closes the open positions of the given pair, or all including adopted holdings if none is
+    // given.
+    pub async fn close_manually(&mut self, prices: &Candles, pair: Option<(Coin, Coin)>) -> usize {
+        let mut closed = 0;
+        if pair.is_none() {
+            closed += self.close_adopted(prices).await;
+        }
+        for i in 0..self.positions.len() {
+            let position = &self.positions[i];
+            let selected = pair.map_or(true, |(long, short)| {
+                position.long == long && position.short == short
+            });
+            if selected && position.is_open() && !position.is_closed() {
+                self.close_at(i, prices, Exit::Manual).await;
+                closed += 1;
+            }
+        }
+        closed
+    }
+
+    // Closes the adopted holdings that have prices, others are kept.
+    async fn close_adopted(&mut self, prices: &Candles) -> usize {
+        let mut closed = 0;
+        for adopted in std::mem::take(&mut self.adopted) {
+            let candle = match prices.iter().find(|candle| candle.coin == adopted.coin) {
+                Some(candle) => candle,
+                None => {
+                    log::warn!("Could not close adopted {}, it has no price.", adopted.coin);
+                    self.adopted.push(adopted);
+                    continue;
+                }
+            };
+            let side = if adopted.size > Decimal::zero() {
+                Side::Sell
+            } else {
+                Side::Buy
+            };
+            let fill = self
+                .executor
+                .execute(
+                    adopted.coin,
+                    side,
+                    candle.close,
+                    adopted.size.abs() * candle.close,
+                )
+                .await;
+            let profit = adopted.size * (fill.price - adopted.price) - fill.fee;
+            self.wallet.update(self.wallet.balance() + profit);
+            self.changes += 1;
+            closed += 1;
+        }
+        closed
+    }
+
+    async fn close_at(&mut self, i: usize, prices: &Candles, exit: Exit) {
+        let position = &mut self.positions[i];
+        let long = self
+            .executor
+            .execute(
+                position.long,
+                Side::Sell,
+                prices.coin(position.long).close,
+                position.market_value(prices) / Decimal::new(2, 0),
+            )
+            .await;
+        let short = self
+            .executor
+            .execute(
+                position.short,
+                Side::Buy,
+                prices.coin(position.short).close,
+                position.market_value(prices) / Decimal::new(2, 0),
+            )
+            .await;
+        self.wallet
+            .put(position.close_filled(prices, long, short, exit));
+        self.changes += 1;
+
+        let profit = position.realized_profit();
+        let severity = if NOTIFIER.is_large_loss(profit) {
+            Severity::Critical
+        } else {
+            Severity::Info
+        };
+        // Per pair, so a large loss on one pair never suppresses that on another.
+        NOTIFIER.notify(
+            severity,
+            &format!("close {}/{}", position.long, position.short),
+            format!(
+                "Closed {}/{} by {:?} with profit {:.2}.",
+                position.long, position.short, exit, profit
+            ),
+        );
+    }
+
+    pub fn update(&mut self, prices: &Candles) {
+        let mut exposure = Decimal::zero();
+        let mut unrealized_profit = Decimal::zero();
         for position in self
             .positions
-            .iter_mut()
+            .iter()
+            .filter(|p| p.is_open() && !p.is_closed())
+        {
+            exposure += position.market_value(prices);
+            unrealized_profit += position.unrealized_profit(prices);
+        }
+        // Futures held on margin only add their profit to the equity.
+        for adopted in &self.adopted {
+            exposure += adopted.unrealized_profit(prices);
+            unrealized_profit += adopted.unrealized_profit(prices);
+        }
+
+        if let Some(candle) = prices.first() {
+            self.wallet.mark(candle.time, exposure, unrealized_profit);
+        }
+    }
+
+    pub fn unrealized_profit(&self, prices: &Candles) -> Decimal {
+        let adopted: Decimal = self
+            .adopted
+            .iter()
+            .map(|adopted| adopted.unrealized_profit(prices))
+            .sum();
+        self.positions
+            .iter()
+            .filter(|p| p.is_open() && !p.is_closed())
+            .map(|p| p.unrealized_profit(prices))
+            .sum::<Decimal>()
+            + adopted
+    }
+
+    pub fn wallet(&self) -> &Wallet {
+        &self.wallet
+    }
+
+    pub fn positions(&self) -> &[Position] {
+        &self.positions
+    }
+
+    pub fn equity(&self) -> &[Equity] {
+        self.wallet.history()
+    }
+
+    // Only keeps the given number of last equity marks.
+    pub fn keep_equity(&mut self, limit: usize) {
+        self.wallet.keep(limit);
+    }
+
+    pub fn total_equity(&self) -> Decimal {
+        self.wallet
+            .equity()
+            .map(|equity| equity.total())
+            .unwrap_or_else(|| self.wallet.balance())
+    }
+
+    // Expected net size per coin of all open positions, including adopted ones.
+    pub fn holdings(&self) -> HashMap<Coin, Decimal> {
+        let mut holdings = HashMap::new();
+        for position in self
+            .positions
+            .iter()
             .filter(|p| p.is_open() && !p.is_closed())
         {
-            if let Some(returned) = position.check_close(&prices) {
-                self.wallet.put(returned);
+            *holdings.entry(position.long).or_insert_with(Decimal::zero) +=
+                position.long_quantity.unwrap() / position.long_open_price.unwrap();
+            *holdings.entry(position.short).or_insert_with(Decimal::zero) -=
+                position.short_quantity.unwrap() / position.short_open_price.unwrap();
+        }
+        for adopted in &self.adopted {
+            *holdings.entry(adopted.coin).or_insert_with(Decimal::zero) += adopted.size;
+        }
+        holdings
+    }
+
+    pub fn adopted(&self) -> &[Adopted] {
+        &self.adopted
+    }
+
+    // Adds the holding at the given price to the adopted one of its coin, which is dropped once
+    // the exchange no longer holds the coin.
+    pub fn adopt(&mut self, holding: Holding, price: Decimal) {
+        self.changes += 1;
+        let i = match self.adopted.iter().position(|a| a.coin == holding.coin) {
+            Some(i) => i,
+            None => {
+                self.adopted.push(Adopted {
+                    coin: holding.coin,
+                    size: holding.size,
+                    price,
+                });
+                return;
             }
+        };
+
+        let adopted = &mut self.adopted[i];
+        let size = adopted.size + holding.size;
+        if size.is_zero() {
+            self.adopted.remove(i);
+            return;
+        }
+        if size.is_sign_negative() != adopted.size.is_sign_negative() {
+            adopted.price = price;
+        } else if size.abs() > adopted.size.abs() {
+            adopted.price = (adopted.size * adopted.price + holding.size * price) / size;
         }
+        adopted.size = size;
+    }
+
+    pub fn adopt_balance(&mut self, total: Decimal) {
+        let exposure = self
+            .wallet
+            .equity()
+            .map(|equity| equity.exposure)
+            .unwrap_or_else(Decimal::zero);
+        self.wallet.update(total - exposure);
+        self.changes += 1;
+    }
+
+    pub fn changes(&self) -> usize {
+        self.changes
     }
 
     pub fn total_realized_profit(&self) -> Decimal {
@@ -66,3 +455,65 @@ impl Investor {
         (wins, losses)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trader::testing::candle;
+    use chrono::Utc;
+
+    #[tokio::test]
+    async fn limits() {
+        let time = Utc::now();
+        let prices = vec![
+            candle(Coin::BTC, 10, time),
+            candle(Coin::ETH, 20, time),
+            candle(Coin::SOL, 5, time),
+        ];
+        let position = |long, short| Position::new(long, short, Decimal::new(5, 2));
+        let mut investor = Investor::new();
+
+        investor.set_paused(true);
+        investor.open(&prices, position(Coin::BTC, Coin::ETH)).await;
+        assert!(investor.positions().is_empty());
+
+        investor.set_paused(false);
+        investor.set_limits(Limits {
+            max_positions: Some(1),
+            max_loss: None,
+        });
+        investor.open(&prices, position(Coin::BTC, Coin::ETH)).await;
+        investor.open(&prices, position(Coin::ETH, Coin::SOL)).await;
+        assert_eq!(investor.positions().len(), 1);
+
+        assert_eq!(investor.close_manually(&prices, None).await, 1);
+        assert!(investor.positions()[0].is_closed());
+        assert_eq!(investor.wallet().borrowed(), 0);
+    }
+
+    #[tokio::test]
+    async fn adopted() {
+        let prices = vec![candle(Coin::BTC, 20, Utc::now())];
+        let holding = |size| Holding {
+            coin: Coin::BTC,
+            size: Decimal::new(size, 0),
+        };
+        let mut investor = Investor::new();
+
+        investor.adopt(holding(2), Decimal::new(10, 0));
+        investor.adopt(holding(2), Decimal::new(20, 0));
+        assert_eq!(investor.adopted()[0].price, Decimal::new(15, 0));
+        investor.update(&prices);
+        assert_eq!(investor.unrealized_profit(&prices), Decimal::new(20, 0));
+        assert_eq!(investor.total_equity(), Decimal::new(120, 0));
+
+        // Dropped once the exchange no longer holds it.
+        investor.adopt(holding(-4), Decimal::new(20, 0));
+        assert!(investor.adopted().is_empty());
+
+        investor.adopt(holding(-2), Decimal::new(25, 0));
+        assert_eq!(investor.close_manually(&prices, None).await, 1);
+        assert!(investor.adopted().is_empty());
+        assert_eq!(investor.wallet().balance(), Decimal::new(110, 0));
+    }
+}
@@ -0,0 +1,201 @@
+use super::{Coin, Config, Fetcher, Investor, Position, Report, Scenario};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+};
+
+// File the historical candles were read from, or generated from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    Cache(PathBuf),
+    Synthetic(PathBuf),
+}
+
+impl Source {
+    pub fn path(&self) -> &Path {
+        match self {
+            Source::Cache(path) | Source::Synthetic(path) => path,
+        }
+    }
+
+    pub fn historical(&self, fetcher: Fetcher) -> Result<Fetcher, String> {
+        match self {
+            Source::Cache(path) => fetcher.cached(path).read_cache(),
+            Source::Synthetic(path) => fetcher.synthetic(&Scenario::load(path)?),
+        }
+    }
+}
+
+// Everything a backtest result depends on, to reproduce and verify it later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: String,
+    // Output of `git describe` when the binary was built.
+    pub commit: Option<String>,
+    pub config: Config,
+    pub coins: Vec<Coin>,
+    pub from: DateTime<Utc>,
+    pub to: Option<DateTime<Utc>>,
+    pub interval: i64,
+    pub source: Source,
+    // SHA-256 of the source file.
+    pub cache: Option<String>,
+    pub report: Report,
+    pub trades: usize,
+    // SHA-256 of all positions as JSON.
+    pub digest: String,
+}
+
+impl Manifest {
+    pub fn new(
+        config: &Config,
+        coins: &[Coin],
+        (from, to): (DateTime<Utc>, Option<DateTime<Utc>>),
+        interval: i64,
+        source: &Source,
+        investor: &Investor,
+    ) -> Self {
+        let positions = investor.positions();
+        Manifest {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            commit: commit(),
+            config: config.clone(),
+            coins: coins.to_vec(),
+            from,
+            to,
+            interval,
+            source: source.clone(),
+            cache: hash_file(source.path()),
+            report: Report::new(investor),
+            trades: positions.len(),
+            digest: digest(positions),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        serde_json::from_reader(BufReader::new(File::open(path)?)).map_err(io::Error::from)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        serde_json::to_writer_pretty(File::create(path)?, self).map_err(io::Error::from)
+    }
+
+    // Fails unless the source still has the recorded hash, so a missing cache is never
+    // downloaded again for a replay.
+    pub fn check_source(&self) -> Result<(), String> {
+        let hash = hash_file(self.source.path());
+        if hash == self.cache {
+            Ok(())
+        } else {
+            Err(format!(
+                "{:?} has hash {:?}, expected {:?}",
+                self.source.path(),
+                hash,
+                self.cache
+            ))
+        }
+    }
+
+    // Compares a rerun with the recorded run, returning the differences. The code version may
+    // differ, as long as the results don't.
+    pub fn verify(&self, replay: &Manifest) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.cache != replay.cache {
+            differences.push(format!("cache {:?} != {:?}", self.cache, replay.cache));
+        }
+        if self.report != replay.report {
+            differences.push(format!("report {:?} != {:?}", self.report, replay.report));
+        }
+        if self.trades != replay.trades || self.digest != replay.digest {
+            differences.push(format!(
+                "{} trades ({}) != {} trades ({})",
+                self.trades, self.digest, replay.trades, replay.digest
+            ));
+        }
+        differences
+    }
+}
+
+// Set by the build script, unless built outside of a git checkout.
+pub fn commit() -> Option<String> {
+    option_env!("GIT_COMMIT").map(str::to_string)
+}
+
+pub fn hash_file<P: AsRef<Path>>(path: P) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).ok()?;
+    Some(hex::encode(hasher.finalize()))
+}
+
+pub fn digest(positions: &[Position]) -> String {
+    hex::encode(Sha256::digest(&serde_json::to_vec(positions).unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn manifest(source: Source, positions: &[Position]) -> Manifest {
+        Manifest {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            commit: None,
+            config: Config::default(),
+            coins: Coin::all(),
+            from: Utc::now(),
+            to: None,
+            interval: 15,
+            cache: hash_file(source.path()),
+            source,
+            report: Report {
+                profit: Decimal::ZERO,
+                wins: 0,
+                losses: 0,
+                final_equity: Decimal::ZERO,
+                max_drawdown: Decimal::ZERO,
+            },
+            trades: positions.len(),
+            digest: digest(positions),
+        }
+    }
+
+    #[test]
+    fn verify() {
+        let positions = vec![Position::new(Coin::BTC, Coin::ETH, Decimal::new(5, 2))];
+        let manifest = manifest(
+            Source::Synthetic(PathBuf::from("scenario.toml")),
+            &positions,
+        );
+        let json = serde_json::to_string(&manifest).unwrap();
+        let loaded: Manifest = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.source, manifest.source);
+        assert!(manifest.verify(&loaded).is_empty());
+
+        let other = vec![Position::new(Coin::ETH, Coin::BTC, Decimal::new(5, 2))];
+        let replay = Manifest {
+            digest: digest(&other),
+            ..loaded
+        };
+        assert_eq!(manifest.verify(&replay).len(), 1);
+    }
+
+    #[test]
+    fn check_source() {
+        let path = std::env::temp_dir().join(format!("cache-{}.bin", rand::random::<u64>()));
+        std::fs::write(&path, b"candles").unwrap();
+        let manifest = manifest(Source::Cache(path.clone()), &[]);
+        assert!(manifest.cache.is_some());
+        assert!(manifest.check_source().is_ok());
+
+        // A missing cache is not downloaded again.
+        std::fs::remove_file(&path).unwrap();
+        let err = manifest.check_source().unwrap_err();
+        assert!(err.contains("has hash None"), "{}", err);
+    }
+}
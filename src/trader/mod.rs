@@ -1,61 +1,494 @@
+mod account;
 mod coin;
+mod config;
+mod executor;
+mod export;
 mod fetcher;
+mod import;
 mod investor;
+mod manifest;
+mod optimizer;
+mod paper;
 mod position;
+mod reconciler;
+mod report;
 mod strategy;
+mod synthetic;
+#[cfg(test)]
+pub(crate) mod testing;
+mod walkforward;
 mod wallet;
 
-use chrono::{DateTime, Duration, Utc};
+use crate::{
+    control::Action,
+    indicators::{Real, Snapshot},
+    notifier::Severity,
+    recorder::Recorder,
+    Number, CONTROL, METRICS, NOTIFIER,
+};
+pub use account::*;
+use chrono::Duration;
 pub use coin::*;
+pub use config::*;
+pub use executor::*;
+pub use export::*;
 pub use fetcher::*;
 use ftx::rest::Rest;
+pub use import::*;
 pub use investor::*;
+pub use manifest::*;
+use once_cell::sync::Lazy;
+pub use optimizer::*;
+pub use paper::*;
 pub use position::*;
-use std::env::var;
+pub use reconciler::*;
+pub use report::*;
+use rust_decimal::prelude::*;
+use std::{
+    env::var,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 pub use strategy::*;
+pub use synthetic::*;
+use tokio::task;
+pub use walkforward::*;
 pub use wallet::*;
 
-pub struct Trader {
+pub fn rest() -> Rest {
+    dotenv::dotenv().ok();
+    let subaccount = Some(var("SUBACCOUNT").expect("SUBACCOUNT is not set."));
+    let key = var("API_KEY").expect("API_KEY is not set.");
+    let secret = var("API_SECRET").expect("API_SECRET is not set.");
+    Rest::new(key, secret, subaccount)
+}
+
+// Exchange client built on first use, so backtests and replays on cached or synthetic candles
+// run without credentials.
+pub type LazyRest = Lazy<Rest>;
+
+pub fn lazy_rest() -> LazyRest {
+    Lazy::new(rest)
+}
+
+// Runs the strategy on the rayon pool without stalling other tasks on this runtime worker. This
+// requires a multi-threaded runtime, `block_in_place` panics on a current thread one.
+fn step<T: Real>(strategy: &mut Strategy<T>, prices: &Candles) -> Option<Position> {
+    task::block_in_place(|| strategy.run(prices))
+}
+
+// Trades on the strategy signal, returning whether it signalled a position.
+async fn tick(investor: &mut Investor, prices: &Candles, signal: Option<Position>) -> bool {
+    let signalled = signal.is_some();
+    if let Some(position) = signal {
+        investor.open(prices, position).await;
+        investor.close(prices).await;
+    }
+    investor.update(prices);
+    signalled
+}
+
+// Runs the strategy over already loaded candles, after warming up its indicators without trading.
+pub async fn simulate<T: Real>(
+    coins: &[Coin],
+    params: Params,
+    costs: Costs,
+    warmup: &[Candles],
+    history: &[Candles],
+) -> Investor {
+    let mut strategy: Strategy<T> = Strategy::new(coins, params);
+    let mut investor = Investor::new().with_executor(Box::new(Simulator::new(costs)));
+    for prices in warmup {
+        strategy.run(prices);
+    }
+    for prices in history {
+        let signal = strategy.run(prices);
+        tick(&mut investor, prices, signal).await;
+    }
+    investor
+}
+
+pub async fn backtest<T: Real>(
+    coins: &[Coin],
+    params: Params,
+    costs: Costs,
+    warmup: &[Candles],
+    history: &[Candles],
+) -> Report {
+    Report::new(&simulate::<T>(coins, params, costs, warmup, history).await)
+}
+
+// Number of candles of the given interval in the period, at least one.
+fn ticks(period: Duration, interval: Duration) -> usize {
+    (period.num_seconds() / interval.num_seconds().max(1)).max(1) as usize
+}
+
+// Sets up the investor to execute and keep its equity as the mode requires.
+fn prepare(investor: Investor, mode: &Mode, config: &Config, interval: Duration) -> Investor {
+    let executor: Box<dyn Executor> = match mode {
+        Mode::Live(exchange) => Box::new(exchange.clone()),
+        _ => Box::new(Simulator::new(config.costs)),
+    };
+    let mut investor = investor.with_executor(executor);
+    // Limits adjusted at runtime are reset to the configured ones on restart.
+    investor.set_limits(config.limits);
+    // Traders that run indefinitely only keep a day of equity, paper trading appends all of it
+    // to its CSV.
+    if !matches!(mode, Mode::Backtest) {
+        investor.keep_equity(ticks(Duration::days(1), interval));
+    }
+    investor
+}
+
+pub enum Mode {
+    // Trade on historical data.
+    Backtest,
+    // Trade on real time data with simulated execution.
+    Paper(Paper),
+    // Trade on real time data on the exchange and reconcile with its account.
+    Live(Exchange),
+}
+
+// Computes the indicators in `T`, chosen by the configured precision.
+pub struct Trader<T: Real = Number> {
+    coins: Vec<Coin>,
     fetcher: Fetcher,
-    strategy: Strategy,
+    strategy: Strategy<T>,
     investor: Investor,
-    rest: Rest,
+    reconciler: Reconciler,
+    mode: Mode,
+    config: Config,
+    rest: LazyRest,
+    snapshot: Option<PathBuf>,
+    snapshot_ticks: usize,
+    saving: Option<task::JoinHandle<()>>,
+    ticks: usize,
+    last: Option<Candles>,
 }
 
-impl Trader {
-    pub fn new(coins: &[Coin], from: DateTime<Utc>, interval: Duration) -> Self {
-        dotenv::dotenv().ok();
-        let subaccount = Some(var("SUBACCOUNT").unwrap());
-        let key = var("API_KEY").unwrap();
-        let secret = var("API_SECRET").unwrap();
-        let rest = Rest::new(key.clone(), secret.clone(), subaccount.clone());
-
+impl<T: Real> Trader<T> {
+    pub fn new(coins: &[Coin], fetcher: Fetcher, config: &Config, mode: Mode) -> Self {
+        let investor = prepare(Investor::new(), &mode, config, fetcher.interval());
         Trader {
-            fetcher: Fetcher::new(coins, from, interval),
-            strategy: Strategy::new(coins),
-            investor: Investor::new(),
-            rest,
+            coins: Coin::sorted(coins),
+            // Snapshots are saved every hour.
+            snapshot_ticks: ticks(Duration::hours(1), fetcher.interval()),
+            fetcher,
+            strategy: Strategy::new(coins, config.params),
+            investor,
+            reconciler: Reconciler::new(config.reconcile, Duration::minutes(5)),
+            mode,
+            config: config.clone(),
+            rest: lazy_rest(),
+            snapshot: None,
+            saving: None,
+            ticks: 0,
+            last: None,
+        }
+    }
+
+    // Continues with the positions and wallet of an earlier run, like a saved paper trader.
+    pub fn with_investor(mut self, investor: Investor) -> Self {
+        self.investor = prepare(investor, &self.mode, &self.config, self.fetcher.interval());
+        self
+    }
+
+    pub fn record(mut self, recorder: &Recorder) -> Self {
+        self.strategy.record(recorder);
+        self
+    }
+
+    // Saves the strategy to the given file periodically, and restores it from there if it was
+    // saved for the same coins and parameters, so only the candles since have to be fetched.
+    pub fn snapshot<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        let path = path.into();
+        if let Some(state) = StrategyState::load(&path, &self.coins, &self.config.params) {
+            if let Some(time) = state.time {
+                self.fetcher.resume(time);
+            }
+            self.strategy.restore(state);
+        }
+        self.snapshot = Some(path);
+        self
+    }
+
+    // Copies the state on the loop, but encodes and writes it in the background, as it takes a
+    // while with hundreds of MB of indicator windows.
+    async fn save_snapshot(&mut self) {
+        if let Some(path) = self.snapshot.clone() {
+            // Waits for the previous one, so they are written in order.
+            self.saved().await;
+            let state = self.strategy.snapshot();
+            self.saving = Some(task::spawn_blocking(move || {
+                if let Err(err) = state.save(path) {
+                    log::error!("Could not save strategy snapshot: {}", err);
+                }
+            }));
+        }
+    }
+
+    async fn saved(&mut self) {
+        if let Some(saving) = self.saving.take() {
+            saving.await.unwrap();
         }
     }
 
-    pub async fn run(mut self) {
+    pub fn investor(&self) -> &Investor {
+        &self.investor
+    }
+
+    // Writes trades and equity to the given directory, valuing open positions at the last prices.
+    pub fn export<P: AsRef<Path>>(&self, dir: P) -> io::Result<()> {
+        export(dir, &self.investor, self.last.as_ref())
+    }
+
+    // Has to run on a multi-threaded runtime, as the strategy blocks its worker.
+    pub async fn run(&mut self) -> Report {
         while let Some(prices) = self.fetcher.next(&self.rest).await {
-            if let Some(position) = self.strategy.run(&prices) {
-                self.investor.open(&prices, position).await;
-                self.investor.close(&prices).await;
+            // Saves the state after the previous candles, before running on the next ones.
+            if self.ticks > 0 && self.ticks.is_multiple_of(self.snapshot_ticks) {
+                self.save_snapshot().await;
+            }
+            self.ticks += 1;
+
+            for request in CONTROL.pending() {
+                let result = self.apply(&request.action, &prices).await;
+                CONTROL.audit(request, result);
             }
+
+            let signalled = match &self.mode {
+                Mode::Backtest => {
+                    let signal = step(&mut self.strategy, &prices);
+                    tick(&mut self.investor, &prices, signal).await
+                }
+                Mode::Paper(paper) => {
+                    // Only warm up the strategy until real time data arrives.
+                    if !self.fetcher.is_realtime() {
+                        step(&mut self.strategy, &prices);
+                        self.observe(&prices, false);
+                        continue;
+                    }
+
+                    let signal = step(&mut self.strategy, &prices);
+                    let signalled = tick(&mut self.investor, &prices, signal).await;
+                    // Keeps trading, the next save may succeed again.
+                    if let Err(err) = paper.save(&self.investor) {
+                        log::error!("Could not save paper trading state: {}", err);
+                        NOTIFIER.notify(
+                            Severity::Warning,
+                            "paper",
+                            format!("Could not save paper trading state: {}", err),
+                        );
+                    }
+                    signalled
+                }
+                Mode::Live(exchange) => {
+                    let signal = step(&mut self.strategy, &prices);
+                    let signalled = tick(&mut self.investor, &prices, signal).await;
+                    self.reconciler
+                        .run(&prices, exchange, &mut self.investor)
+                        .await;
+                    signalled
+                }
+            };
+            self.observe(&prices, signalled);
+            self.last = Some(prices);
+        }
+
+        self.strategy.flush();
+        self.save_snapshot().await;
+        self.saved().await;
+
+        let report = Report::new(&self.investor);
+        report.log();
+        report
+    }
+
+    async fn apply(&mut self, action: &Action, prices: &Candles) -> String {
+        match *action {
+            Action::Pause => self.investor.set_paused(true),
+            Action::Resume => self.investor.set_paused(false),
+            Action::Close { long, short } => {
+                let closed = self
+                    .investor
+                    .close_manually(prices, Some((long, short)))
+                    .await;
+                return format!("closed {} positions", closed);
+            }
+            Action::CloseAll => {
+                let closed = self.investor.close_manually(prices, None).await;
+                return format!("closed {} positions", closed);
+            }
+            Action::Blacklist { coin } => self.strategy.blacklist(coin, true),
+            Action::Whitelist { coin } => self.strategy.blacklist(coin, false),
+            Action::BlacklistPair { long, short } => {
+                self.strategy.blacklist_pair(long, short, true)
+            }
+            Action::WhitelistPair { long, short } => {
+                self.strategy.blacklist_pair(long, short, false)
+            }
+            Action::SetLimits { limits } => self.investor.set_limits(limits),
+        }
+        "ok".to_string()
+    }
+
+    // Updates the exported metrics, only for the trader itself and not for parallel backtests.
+    fn observe(&self, prices: &Candles, signalled: bool) {
+        for candle in prices {
+            METRICS.candles.coin(candle.coin).inc();
+        }
+        if signalled {
+            METRICS.signals.inc();
+        }
+
+        let open = self
+            .investor
+            .positions()
+            .iter()
+            .filter(|p| p.is_open() && !p.is_closed())
+            .count();
+        let wallet = self.investor.wallet();
+        METRICS.positions_open.set(open as f64);
+        METRICS
+            .realized_profit
+            .set(self.investor.total_realized_profit().to_f64().unwrap());
+        METRICS
+            .unrealized_profit
+            .set(self.investor.unrealized_profit(prices).to_f64().unwrap());
+        METRICS.balance.set(wallet.balance().to_f64().unwrap());
+        METRICS
+            .free_parts
+            .set((wallet.parts() - wallet.borrowed()) as f64);
+
+        #[cfg(feature = "api")]
+        if crate::API.is_enabled() {
+            self.publish(prices);
         }
+    }
+
+    #[cfg(feature = "api")]
+    fn publish(&self, prices: &Candles) {
+        use crate::api::{OpenPosition, Status, WalletStatus};
+
+        let positions = self.investor.positions();
+        let wallet = self.investor.wallet();
+        let status = Status {
+            time: prices.first().map(|candle| candle.time),
+            positions: positions
+                .iter()
+                .filter(|p| p.is_open() && !p.is_closed())
+                .map(|position| OpenPosition {
+                    position: position.clone(),
+                    unrealized_profit: position.unrealized_profit(prices),
+                })
+                .collect(),
+            wallet: Some(WalletStatus {
+                balance: wallet.balance(),
+                parts: wallet.parts(),
+                borrowed: wallet.borrowed(),
+                equity: wallet.equity().copied(),
+            }),
+            signals: self.strategy.signals().iter().copied().collect(),
+            freshness: self.fetcher.freshness(),
+            trades: Vec::new(),
+        };
+        crate::API.publish(status, positions);
+    }
+
+    pub async fn optimize<P: AsRef<Path>>(
+        mut self,
+        search: Search,
+        out: P,
+    ) -> io::Result<Vec<(Params, Report)>> {
+        let candidates = search.candidates();
+        log::info!("Optimizing over {} parameter sets.", candidates.len());
+
+        let history = self.fetcher.history(&self.rest).await;
+        let results = Optimizer::<T>::new(&self.coins, self.config.costs, Arc::new(history))
+            .run(candidates)
+            .await;
+
+        // Logs the best parameters first, so they are known even if writing the results fails.
+        if let Some((params, report)) = results.first() {
+            log::info!("BEST PARAMETERS: \t{:?}", params);
+            report.log();
+        }
+        write_results(out.as_ref().join("optimize.csv"), &results)?;
+        Ok(results)
+    }
+
+    pub async fn walk_forward<P: AsRef<Path>>(
+        mut self,
+        search: Search,
+        walk_forward: WalkForward,
+        out: P,
+    ) -> io::Result<Validation> {
+        let history = self.fetcher.history(&self.rest).await;
+        let optimizer = Optimizer::<T>::new(&self.coins, self.config.costs, Arc::new(history));
+        let validation = walk_forward.run(&optimizer, &search).await;
+        validation.log();
+        validation.write(out)?;
+        Ok(validation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn run() {
+        let coins = [Coin::BTC, Coin::ETH];
+        let scenario = Scenario {
+            seed: 3,
+            candles: 50,
+            markets: vec![
+                Market {
+                    coin: Coin::BTC,
+                    price: Decimal::new(100, 0),
+                    volume: Decimal::ONE,
+                    process: Process::Gbm {
+                        drift: 0.0,
+                        volatility: 0.01,
+                        correlation: 0.0,
+                    },
+                },
+                Market {
+                    coin: Coin::ETH,
+                    price: Decimal::new(50, 0),
+                    volume: Decimal::ONE,
+                    process: Process::Cointegrated {
+                        with: Coin::BTC,
+                        beta: 1.0,
+                        half_life: 5.0,
+                        volatility: 0.01,
+                    },
+                },
+            ],
+            ..Scenario::default()
+        };
+        let from = Utc.ymd(2021, 1, 1).and_hms(0, 0, 0);
+        let interval = Duration::seconds(15);
+        let fetcher = Fetcher::new(&coins, from, interval)
+            .until(from + interval * 50)
+            .synthetic(&scenario)
+            .unwrap();
+        let mut config = Config::default();
+        config.params = Params {
+            mov_period: 3,
+            corr_period: 5,
+            min_corr: -2.0,
+            stdev_mult: 0.5,
+            min_diff: 0.0,
+            max_diff: 1.0,
+        };
 
-        log::info!(
-            "TOTAL PROFIT: \t{:.2}",
-            self.investor.total_realized_profit()
-        );
-        let (wins, losses) = self.investor.wins_losses();
-        log::info!(
-            "WIN/LOSS:     \t{}/{} ({:.2}%)",
-            wins,
-            losses,
-            wins as f32 / (wins + losses) as f32 * 100.0
-        );
+        let mut trader = Trader::<Number>::new(&coins, fetcher, &config, Mode::Backtest);
+        let report = trader.run().await;
+        assert_eq!(trader.investor().equity().len(), 50);
+        assert_eq!(report.final_equity, trader.investor().equity()[49].total());
+        assert!(trader.last.is_some());
     }
 }
@@ -0,0 +1,287 @@
+use super::{backtest, Candles, Coin, Costs, Params, Report};
+use crate::{indicators::Real, journal::silently, Number};
+use futures::{executor::block_on, stream, StreamExt};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File, io, io::Write, marker::PhantomData, ops::Range, path::Path, sync::Arc,
+    thread::available_parallelism,
+};
+use tokio::task::spawn_blocking;
+
+// Values to try for each parameter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Grid {
+    pub mov_period: Vec<usize>,
+    pub corr_period: Vec<usize>,
+    pub min_corr: Vec<Number>,
+    pub stdev_mult: Vec<Number>,
+    pub min_diff: Vec<Number>,
+    pub max_diff: Vec<Number>,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        let day = 60 * 60 * 24 / 15;
+        Grid {
+            mov_period: vec![day / 4, day, day * 4],
+            corr_period: vec![day * 7, day * 30],
+            min_corr: vec![0.9, 0.95],
+            stdev_mult: vec![2.0, 2.5, 3.0],
+            min_diff: vec![0.025, 0.05],
+            max_diff: vec![0.1],
+        }
+    }
+}
+
+impl Grid {
+    // Every combination of the given values.
+    pub fn params(&self) -> Vec<Params> {
+        let mut params = Vec::new();
+        for &mov_period in &self.mov_period {
+            for &corr_period in &self.corr_period {
+                for &min_corr in &self.min_corr {
+                    for &stdev_mult in &self.stdev_mult {
+                        for &min_diff in &self.min_diff {
+                            for &max_diff in &self.max_diff {
+                                params.push(Params {
+                                    mov_period,
+                                    corr_period,
+                                    min_corr,
+                                    stdev_mult,
+                                    min_diff,
+                                    max_diff,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        params
+    }
+
+    // Samples uniformly between the smallest and largest value of each parameter. Like the full
+    // grid, there is nothing to sample if a parameter has no values.
+    pub fn sample(&self, samples: usize, seed: u64) -> Vec<Params> {
+        if self.mov_period.is_empty()
+            || self.corr_period.is_empty()
+            || self.min_corr.is_empty()
+            || self.stdev_mult.is_empty()
+            || self.min_diff.is_empty()
+            || self.max_diff.is_empty()
+        {
+            return Vec::new();
+        }
+
+        fn periods<R: Rng>(rng: &mut R, values: &[usize]) -> usize {
+            let min = *values.iter().min().unwrap();
+            let max = *values.iter().max().unwrap();
+            rng.gen_range(min..=max)
+        }
+
+        fn floats<R: Rng>(rng: &mut R, values: &[Number]) -> Number {
+            let min = values.iter().copied().fold(Number::INFINITY, Number::min);
+            let max = values
+                .iter()
+                .copied()
+                .fold(Number::NEG_INFINITY, Number::max);
+            if min == max {
+                min
+            } else {
+                rng.gen_range(min..=max)
+            }
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..samples)
+            .map(|_| Params {
+                mov_period: periods(&mut rng, &self.mov_period),
+                corr_period: periods(&mut rng, &self.corr_period),
+                min_corr: floats(&mut rng, &self.min_corr),
+                stdev_mult: floats(&mut rng, &self.stdev_mult),
+                min_diff: floats(&mut rng, &self.min_diff),
+                max_diff: floats(&mut rng, &self.max_diff),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Search {
+    Grid(Grid),
+    Random {
+        grid: Grid,
+        samples: usize,
+        seed: u64,
+    },
+}
+
+impl Search {
+    pub fn candidates(&self) -> Vec<Params> {
+        match self {
+            Search::Grid(grid) => grid.params(),
+            Search::Random {
+                grid,
+                samples,
+                seed,
+            } => grid.sample(*samples, *seed),
+        }
+    }
+}
+
+// Candles to warm up on before trading on the given range, as many as the parameters need and
+// the history has.
+pub fn warmup(range: &Range<usize>, params: &Params) -> Range<usize> {
+    range.start.saturating_sub(params.warmup())..range.start
+}
+
+// Backtests in `T`, like the trader it optimizes for.
+pub struct Optimizer<T: Real = Number> {
+    coins: Vec<Coin>,
+    costs: Costs,
+    history: Arc<Vec<Candles>>,
+    threads: usize,
+    real: PhantomData<T>,
+}
+
+impl<T: Real> Optimizer<T> {
+    pub fn new(coins: &[Coin], costs: Costs, history: Arc<Vec<Candles>>) -> Self {
+        Optimizer {
+            coins: coins.to_vec(),
+            costs,
+            history,
+            threads: available_parallelism().map(|n| n.get()).unwrap_or(1),
+            real: PhantomData,
+        }
+    }
+
+    pub fn history(&self) -> &[Candles] {
+        &self.history
+    }
+
+    pub fn coins(&self) -> &[Coin] {
+        &self.coins
+    }
+
+    pub fn costs(&self) -> Costs {
+        self.costs
+    }
+
+    // Backtests all candidates and ranks them by realized profit.
+    pub async fn run(&self, candidates: Vec<Params>) -> Vec<(Params, Report)> {
+        self.run_range(candidates, 0..self.history.len()).await
+    }
+
+    // Same as `run`, but only trades on the given range of the history, after warming up on the
+    // candles before it.
+    pub async fn run_range(
+        &self,
+        candidates: Vec<Params>,
+        range: Range<usize>,
+    ) -> Vec<(Params, Report)> {
+        let total = candidates.len();
+        let mut results: Vec<(usize, Params, Report)> =
+            stream::iter(candidates.into_iter().enumerate())
+                .map(|(index, params)| {
+                    let coins = self.coins.clone();
+                    let costs = self.costs;
+                    let history = self.history.clone();
+                    let range = range.clone();
+                    spawn_blocking(move || {
+                        (
+                            index,
+                            params,
+                            block_on(silently(backtest::<T>(
+                                &coins,
+                                params,
+                                costs,
+                                &history[warmup(&range, &params)],
+                                &history[range],
+                            ))),
+                        )
+                    })
+                })
+                .buffer_unordered(self.threads)
+                .enumerate()
+                .map(|(i, result)| {
+                    let (index, params, report) = result.unwrap();
+                    log::info!(
+                        "Backtest {}/{} \t{:?} \tPROFIT = {:.2}",
+                        i + 1,
+                        total,
+                        params,
+                        report.profit
+                    );
+                    (index, params, report)
+                })
+                .collect()
+                .await;
+
+        // Backtests finish in any order, ties are ranked in the order of the candidates.
+        results.sort_by(|(i, _, a), (j, _, b)| b.profit.cmp(&a.profit).then(i.cmp(j)));
+        results
+            .into_iter()
+            .map(|(_, params, report)| (params, report))
+            .collect()
+    }
+}
+
+pub fn write_results<P: AsRef<Path>>(path: P, results: &[(Params, Report)]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(
+        file,
+        "rank,mov_period,corr_period,min_corr,stdev_mult,min_diff,max_diff,profit,final_equity,max_drawdown,wins,losses,win_rate"
+    )?;
+    for (i, (params, report)) in results.iter().enumerate() {
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            i + 1,
+            params.mov_period,
+            params.corr_period,
+            params.min_corr,
+            params.stdev_mult,
+            params.min_diff,
+            params.max_diff,
+            report.profit,
+            report.final_equity,
+            report.max_drawdown,
+            report.wins,
+            report.losses,
+            report.win_rate()
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid() {
+        let grid = Grid::default();
+        assert_eq!(grid.params().len(), 3 * 2 * 2 * 3 * 2);
+    }
+
+    #[test]
+    fn sample() {
+        let grid = Grid::default();
+        let params = grid.sample(100, 0);
+        assert_eq!(params, grid.sample(100, 0));
+        for p in params {
+            assert!(grid.mov_period[0] <= p.mov_period && p.mov_period <= grid.mov_period[2]);
+            assert!(0.9 <= p.min_corr && p.min_corr <= 0.95);
+            assert_eq!(p.max_diff, 0.1);
+        }
+
+        let empty = Grid {
+            min_diff: Vec::new(),
+            ..Grid::default()
+        };
+        assert!(empty.params().is_empty());
+        assert!(empty.sample(100, 0).is_empty());
+    }
+}
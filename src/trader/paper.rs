@@ -0,0 +1,160 @@
+use super::{Equity, Investor};
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use rust_decimal::Decimal;
+use std::{
+    fs::{rename, File, OpenOptions},
+    io,
+    io::Write,
+    path::PathBuf,
+    str::FromStr,
+    sync::Mutex,
+};
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Persists the state of a paper trading investor between runs.
+pub struct Paper {
+    path: PathBuf,
+    // Changes of the investor when it was last saved.
+    saved: Mutex<Option<usize>>,
+}
+
+impl Paper {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Paper {
+            path: path.into(),
+            saved: Mutex::new(None),
+        }
+    }
+
+    fn equity_path(&self) -> PathBuf {
+        self.path.with_extension("csv")
+    }
+
+    // Nothing to restore without a state, but a state that can't be read is an error.
+    pub fn load(&self) -> io::Result<Option<Investor>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        log::info!("Restoring paper trading state from {:?}.", self.path);
+        serde_cbor::from_reader(file)
+            .map(Some)
+            .map_err(|err| invalid(format!("{:?}: {}", self.path, err)))
+    }
+
+    // Equity after every candle of all runs, as the state only keeps the current wallet.
+    pub fn equity(&self) -> io::Result<Vec<Equity>> {
+        let path = self.equity_path();
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut history = Vec::new();
+        for record in csv::Reader::from_path(&path)?.records() {
+            let record = record?;
+            let field = |i: usize| record.get(i).unwrap_or("");
+            let decimal = |i: usize| {
+                Decimal::from_str(field(i)).map_err(|err| invalid(format!("{:?}: {}", path, err)))
+            };
+            // Written as displayed, like `2021-01-01 00:00:00 UTC`.
+            let time = NaiveDateTime::parse_from_str(
+                field(0).trim_end_matches(" UTC"),
+                "%Y-%m-%d %H:%M:%S%.f",
+            )
+            .map_err(|err| invalid(format!("{:?}: {}", path, err)))?;
+            history.push(Equity {
+                time: Utc.from_utc_datetime(&time),
+                cash: decimal(2)?,
+                exposure: decimal(3)?,
+                unrealized_profit: decimal(4)?,
+            });
+        }
+        Ok(history)
+    }
+
+    // Only rewrites the state when the positions or cash changed, but appends the equity
+    // every time.
+    pub fn save(&self, investor: &Investor) -> io::Result<()> {
+        let mut saved = self.saved.lock().unwrap();
+        if *saved != Some(investor.changes()) {
+            // Write to a temporary file first so a crash never leaves a corrupt snapshot.
+            let tmp = self.path.with_extension("tmp");
+            serde_cbor::to_writer(File::create(&tmp)?, investor)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+            rename(&tmp, &self.path)?;
+            *saved = Some(investor.changes());
+        }
+
+        if let Some(equity) = investor.equity().last() {
+            let path = self.equity_path();
+            let new = !path.exists();
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            if new {
+                writeln!(file, "time,total,cash,exposure,unrealized_profit")?;
+            }
+            writeln!(
+                file,
+                "{},{},{},{},{}",
+                equity.time,
+                equity.total(),
+                equity.cash,
+                equity.exposure,
+                equity.unrealized_profit
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trader::{testing::prices, Coin, Position};
+    use chrono::Utc;
+    use std::env::temp_dir;
+
+    #[tokio::test]
+    async fn restore() {
+        let path = temp_dir().join(format!("paper-{}.cbor", rand::random::<u64>()));
+        let paper = Paper::new(&path);
+        assert!(paper.load().unwrap().is_none());
+
+        let prices = prices(10, 20, Utc::now());
+
+        let mut investor = Investor::new();
+        investor
+            .open(
+                &prices,
+                Position::new(Coin::BTC, Coin::ETH, Decimal::new(1, 1)),
+            )
+            .await;
+        investor.update(&prices);
+        paper.save(&investor).unwrap();
+        // Unchanged, so only the equity is appended.
+        std::fs::remove_file(&path).unwrap();
+        paper.save(&investor).unwrap();
+        assert!(!path.exists());
+        investor.set_paused(true);
+        paper.save(&investor).unwrap();
+
+        let restored = paper.load().unwrap().unwrap();
+        assert_eq!(restored.holdings(), investor.holdings());
+        assert_eq!(
+            restored.unrealized_profit(&prices),
+            investor.unrealized_profit(&prices)
+        );
+        let equity = paper.equity().unwrap();
+        assert_eq!(equity.len(), 3);
+        assert_eq!(equity[2], *investor.equity().last().unwrap());
+
+        std::fs::write(&path, b"corrupt").unwrap();
+        assert!(paper.load().is_err());
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(paper.equity_path()).unwrap();
+    }
+}
@@ -1,7 +1,21 @@
-use super::{Candles, Coin};
+use super::{ByCoin, Candles, Coin, Fill};
+use crate::{
+    journal::{is_silent, Event},
+    JOURNAL,
+};
 use chrono::{DateTime, Utc};
 use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Exit {
+    TakeProfit,
+    StopLoss,
+    Manual,
+}
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Position {
     pub open_time: Option<DateTime<Utc>>,
     pub close_time: Option<DateTime<Utc>>,
@@ -15,6 +29,8 @@ pub struct Position {
     pub short_quantity: Option<Decimal>,
     pub take_profit: Decimal,
     pub stop_loss: Decimal,
+    pub fees: Decimal,
+    pub exit: Option<Exit>,
 }
 
 impl Position {
@@ -32,6 +48,8 @@ impl Position {
             short_quantity: None,
             take_profit: diff,
             stop_loss: -diff,
+            fees: Decimal::zero(),
+            exit: None,
         }
     }
 
@@ -43,83 +61,126 @@ impl Position {
         self.close_time.is_some()
     }
 
+    // Opens at the current close prices without any fees.
     pub fn open(&mut self, prices: &Candles, amount: Decimal) {
+        let long = Fill {
+            price: prices.coin(self.long).close,
+            fee: Decimal::zero(),
+        };
+        let short = Fill {
+            price: prices.coin(self.short).close,
+            fee: Decimal::zero(),
+        };
+        self.open_filled(prices, amount, long, short);
+    }
+
+    pub fn open_filled(&mut self, prices: &Candles, amount: Decimal, long: Fill, short: Fill) {
         assert!(!self.is_open());
         assert!(!self.is_closed());
 
         let half = amount / Decimal::new(2, 0);
-        let long_price = prices[self.long as usize].close;
-        let short_price = prices[self.short as usize].close;
 
-        self.long_open_price = Some(long_price);
-        self.short_open_price = Some(short_price);
+        self.long_open_price = Some(long.price);
+        self.short_open_price = Some(short.price);
         self.long_quantity = Some(half);
         self.short_quantity = Some(half);
+        self.fees += long.fee + short.fee;
 
-        assert_eq!(
-            prices[self.long as usize].time,
-            prices[self.short as usize].time
-        );
-        let time = prices[self.long as usize].time;
+        assert_eq!(prices.coin(self.long).time, prices.coin(self.short).time);
+        let time = prices.coin(self.long).time;
 
         self.open_time = Some(time);
 
-        log::info!(
-            "OPEN  \t{}/{} \t= {:.4} \t@ {}",
-            self.long,
-            self.short,
-            long_price / short_price,
-            time
-        );
+        // Quiet like the journal, for backtests that run next to each other.
+        if !is_silent() {
+            log::info!(
+                "OPEN  \t{}/{} \t= {:.4} \t@ {}",
+                self.long,
+                self.short,
+                long.price / short.price,
+                time
+            );
+        }
+        JOURNAL.record(Event::Open {
+            time,
+            long: self.long,
+            short: self.short,
+            long_price: long.price,
+            short_price: short.price,
+            amount,
+            fees: self.fees,
+        });
     }
 
-    pub fn check_close(&mut self, prices: &Candles) -> Option<Decimal> {
+    pub fn should_close(&self, prices: &Candles) -> Option<Exit> {
         assert!(self.is_open());
         assert!(!self.is_closed());
 
         if self.potential_profit_prices(prices) > self.take_profit {
-            // Take profit.
-            return Some(self.close(prices));
+            Some(Exit::TakeProfit)
         } else if self.potential_profit_prices(prices) < self.stop_loss {
-            // Stop loss.
-            return Some(self.close(prices));
+            Some(Exit::StopLoss)
+        } else {
+            None
         }
-
-        None
     }
 
+    // Closes at the current close prices without any fees.
     pub fn close(&mut self, prices: &Candles) -> Decimal {
-        let long_price = prices[self.long as usize].close;
-        let short_price = prices[self.short as usize].close;
+        let long = Fill {
+            price: prices.coin(self.long).close,
+            fee: Decimal::zero(),
+        };
+        let short = Fill {
+            price: prices.coin(self.short).close,
+            fee: Decimal::zero(),
+        };
+        self.close_filled(prices, long, short, Exit::Manual)
+    }
 
-        self.long_close_price = Some(long_price);
-        self.short_close_price = Some(short_price);
+    pub fn close_filled(
+        &mut self,
+        prices: &Candles,
+        long: Fill,
+        short: Fill,
+        exit: Exit,
+    ) -> Decimal {
+        self.long_close_price = Some(long.price);
+        self.exit = Some(exit);
+        self.short_close_price = Some(short.price);
+        self.fees += long.fee + short.fee;
 
-        assert_eq!(
-            prices[self.long as usize].time,
-            prices[self.short as usize].time
-        );
-        let time = prices[self.long as usize].time;
+        assert_eq!(prices.coin(self.long).time, prices.coin(self.short).time);
+        let time = prices.coin(self.long).time;
 
         self.close_time = Some(time);
 
-        log::info!(
-            "CLOSE \t{}/{} \t= {:.4} \t@ {} \t PROFIT = {:.2}%",
-            self.long,
-            self.short,
-            long_price / short_price,
+        if !is_silent() {
+            log::info!(
+                "CLOSE \t{}/{} \t= {:.4} \t@ {} \t PROFIT = {:.2}%",
+                self.long,
+                self.short,
+                long.price / short.price,
+                time,
+                self.realized_profit()
+            );
+        }
+        JOURNAL.record(Event::Close {
             time,
-            self.realized_profit()
-        );
+            long: self.long,
+            short: self.short,
+            long_price: long.price,
+            short_price: short.price,
+            exit,
+            fees: self.fees,
+            profit: self.realized_profit(),
+        });
 
         self.realized_returns()
     }
 
     pub fn potential_profit_prices(&self, prices: &Candles) -> Decimal {
-        self.potential_profit(
-            prices[self.long as usize].close,
-            prices[self.short as usize].close,
-        )
+        self.potential_profit(prices.coin(self.long).close, prices.coin(self.short).close)
     }
 
     pub fn potential_profit(
@@ -134,6 +195,20 @@ impl Position {
             + (self.short_open_price.unwrap() - short_close_price) / self.short_open_price.unwrap()
     }
 
+    pub fn market_value(&self, prices: &Candles) -> Decimal {
+        assert!(self.is_open());
+        assert!(!self.is_closed());
+
+        self.long_quantity.unwrap() / self.long_open_price.unwrap() * prices.coin(self.long).close
+            + self.short_quantity.unwrap() / prices.coin(self.short).close
+                * self.short_open_price.unwrap()
+            - self.fees
+    }
+
+    pub fn unrealized_profit(&self, prices: &Candles) -> Decimal {
+        self.market_value(prices) - self.long_quantity.unwrap() - self.short_quantity.unwrap()
+    }
+
     pub fn realized_returns(&self) -> Decimal {
         assert!(self.is_open());
         assert!(self.is_closed());
@@ -141,24 +216,21 @@ impl Position {
         self.long_quantity.unwrap() / self.long_open_price.unwrap() * self.long_close_price.unwrap()
             + self.short_quantity.unwrap() / self.short_close_price.unwrap()
                 * self.short_open_price.unwrap()
+            - self.fees
     }
 
     pub fn realized_profit(&self) -> Decimal {
         assert!(self.is_open());
         assert!(self.is_closed());
 
-        self.long_quantity.unwrap() / self.long_open_price.unwrap() * self.long_close_price.unwrap()
-            - self.long_quantity.unwrap()
-            + self.short_quantity.unwrap() / self.short_close_price.unwrap()
-                * self.short_open_price.unwrap()
-            - self.short_quantity.unwrap()
+        self.realized_returns() - self.long_quantity.unwrap() - self.short_quantity.unwrap()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::trader::fetcher::Candle;
+    use crate::trader::{fetcher::Candle, testing::prices};
     use chrono::TimeZone;
 
     #[tokio::test]
@@ -166,6 +238,7 @@ mod tests {
         let time = Utc.ymd(2021, 1, 1).and_hms(0, 0, 0);
 
         let btc_open = Candle {
+            coin: Coin::BTC,
             close: Decimal::new(10, 0),
             //high: Decimal::zero(),
             //low: Decimal::zero(),
@@ -175,6 +248,7 @@ mod tests {
         };
 
         let eth_open = Candle {
+            coin: Coin::ETH,
             close: Decimal::new(20, 0),
             //high: Decimal::zero(),
             //low: Decimal::zero(),
@@ -184,6 +258,7 @@ mod tests {
         };
 
         let btc_close = Candle {
+            coin: Coin::BTC,
             close: Decimal::new(20, 0),
             //high: Decimal::zero(),
             //low: Decimal::zero(),
@@ -193,6 +268,7 @@ mod tests {
         };
 
         let eth_close = Candle {
+            coin: Coin::ETH,
             close: Decimal::new(10, 0),
             //high: Decimal::zero(),
             //low: Decimal::zero(),
@@ -203,6 +279,30 @@ mod tests {
 
         let mut pos = Position::new(Coin::BTC, Coin::ETH, Decimal::zero());
         pos.open(&vec![btc_open, eth_open], Decimal::new(20, 0));
+        assert_eq!(
+            pos.market_value(&vec![btc_close, eth_close]),
+            Decimal::new(40, 0)
+        );
+        assert_eq!(
+            pos.unrealized_profit(&vec![btc_close, eth_close]),
+            Decimal::new(20, 0)
+        );
         assert_eq!(pos.close(&vec![btc_close, eth_close]), Decimal::new(40, 0));
     }
+
+    #[tokio::test]
+    async fn test_fees() {
+        let time = Utc.ymd(2021, 1, 1).and_hms(0, 0, 0);
+        let fill = |price| Fill {
+            price: Decimal::new(price, 0),
+            fee: Decimal::new(1, 0),
+        };
+
+        let prices = prices(10, 10, time);
+        let mut pos = Position::new(Coin::BTC, Coin::ETH, Decimal::zero());
+        pos.open_filled(&prices, Decimal::new(20, 0), fill(10), fill(10));
+        assert_eq!(pos.unrealized_profit(&prices), Decimal::new(-2, 0));
+        pos.close_filled(&prices, fill(10), fill(10), Exit::Manual);
+        assert_eq!(pos.realized_profit(), Decimal::new(-4, 0));
+    }
 }
@@ -0,0 +1,288 @@
+use super::{Account, Candles, Coin, Holding, Investor};
+use crate::{notifier::Severity, NOTIFIER};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    // Only report mismatches.
+    Ignore,
+    // Take over the state of the exchange as the local state.
+    Adopt,
+    // Close positions on the exchange that are not known locally.
+    Flatten,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy::Ignore
+    }
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ignore" => Ok(Policy::Ignore),
+            "adopt" => Ok(Policy::Adopt),
+            "flatten" => Ok(Policy::Flatten),
+            _ => Err(format!("Unknown reconciliation policy {}.", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mismatch {
+    Balance {
+        local: Decimal,
+        remote: Decimal,
+    },
+    Holding {
+        coin: Coin,
+        local: Decimal,
+        remote: Decimal,
+    },
+}
+
+pub struct Reconciler {
+    policy: Policy,
+    interval: Duration,
+    tolerance: Decimal,
+    last: Option<DateTime<Utc>>,
+}
+
+impl Reconciler {
+    pub fn new(policy: Policy, interval: Duration) -> Self {
+        Reconciler {
+            policy,
+            interval,
+            tolerance: Decimal::new(1, 2),
+            last: None,
+        }
+    }
+
+    pub fn is_due(&self, time: DateTime<Utc>) -> bool {
+        self.last
+            .map(|last| time - last >= self.interval)
+            .unwrap_or(true)
+    }
+
+    pub async fn run<A: Account + Sync>(
+        &mut self,
+        prices: &Candles,
+        account: &A,
+        investor: &mut Investor,
+    ) -> Vec<Mismatch> {
+        let time = prices[0].time;
+        if !self.is_due(time) {
+            return Vec::new();
+        }
+        self.last = Some(time);
+
+        let mut mismatches = Vec::new();
+
+        match account.balance().await {
+            Ok(remote) => {
+                let local = investor.total_equity();
+                if (remote - local).abs() > self.tolerance * remote.abs().max(Decimal::one()) {
+                    log::warn!(
+                        "Balance mismatch: local {:.2}, remote {:.2}.",
+                        local,
+                        remote
+                    );
+                    mismatches.push(Mismatch::Balance { local, remote });
+                    if self.policy == Policy::Adopt {
+                        investor.adopt_balance(remote);
+                    }
+                }
+            }
+            Err(err) => {
+                log::error!("Could not fetch account balance: {}", err);
+                NOTIFIER.notify(
+                    Severity::Warning,
+                    "exchange",
+                    format!("Could not fetch account balance: {}", err),
+                );
+            }
+        }
+
+        match account.holdings().await {
+            Ok(remote) => {
+                let local = investor.holdings();
+                let mut coins: Vec<Coin> = local
+                    .keys()
+                    .chain(remote.iter().map(|holding| &holding.coin))
+                    .copied()
+                    .collect();
+                coins.sort_by_key(|&coin| coin as usize);
+                coins.dedup();
+
+                for coin in coins {
+                    let local = local.get(&coin).copied().unwrap_or_else(Decimal::zero);
+                    let remote = remote
+                        .iter()
+                        .filter(|holding| holding.coin == coin)
+                        .map(|holding| holding.size)
+                        .sum::<Decimal>();
+                    if (remote - local).abs() <= self.tolerance * local.abs() {
+                        continue;
+                    }
+
+                    log::warn!(
+                        "Position mismatch for {}: local {}, remote {}.",
+                        coin,
+                        local,
+                        remote
+                    );
+                    mismatches.push(Mismatch::Holding {
+                        coin,
+                        local,
+                        remote,
+                    });
+
+                    let orphan = Holding {
+                        coin,
+                        size: remote - local,
+                    };
+                    match self.policy {
+                        Policy::Ignore => {}
+                        // Valued from the current price on.
+                        Policy::Adopt => match prices.iter().find(|candle| candle.coin == coin) {
+                            Some(candle) => investor.adopt(orphan, candle.close),
+                            None => log::warn!("Could not adopt {}, it has no price.", coin),
+                        },
+                        Policy::Flatten if local.is_zero() => {
+                            if let Err(err) = account.flatten(orphan).await {
+                                log::error!("Could not flatten {}: {}", coin, err);
+                                NOTIFIER.notify(
+                                    Severity::Critical,
+                                    "exchange",
+                                    format!("Could not flatten {}: {}", coin, err),
+                                );
+                            }
+                        }
+                        Policy::Flatten => {}
+                    }
+                }
+            }
+            Err(err) => {
+                log::error!("Could not fetch positions: {}", err);
+                NOTIFIER.notify(
+                    Severity::Warning,
+                    "exchange",
+                    format!("Could not fetch positions: {}", err),
+                );
+            }
+        }
+
+        if !mismatches.is_empty() {
+            NOTIFIER.notify(
+                Severity::Warning,
+                "reconcile",
+                format!("Found mismatches with the exchange: {:?}", mismatches),
+            );
+        }
+
+        mismatches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trader::{
+        testing::{exchange, prices},
+        Position,
+    };
+
+    fn investor() -> Investor {
+        let prices = prices(10, 20, Utc::now());
+
+        let mut investor = Investor::new();
+        futures::executor::block_on(investor.open(
+            &prices,
+            Position::new(Coin::BTC, Coin::ETH, Decimal::new(1, 1)),
+        ));
+        investor.update(&prices);
+        investor
+    }
+
+    fn holding(coin: Coin, size: Decimal) -> Holding {
+        Holding { coin, size }
+    }
+
+    #[tokio::test]
+    async fn in_sync() {
+        let mut investor = investor();
+        let (account, orders) = exchange(
+            100,
+            &[
+                holding(Coin::BTC, Decimal::new(25, 1)),
+                holding(Coin::ETH, Decimal::new(-125, 2)),
+            ],
+        )
+        .await;
+
+        let mut reconciler = Reconciler::new(Policy::Flatten, Duration::minutes(5));
+        assert!(reconciler
+            .run(&prices(10, 20, Utc::now()), &account, &mut investor)
+            .await
+            .is_empty());
+        assert!(orders.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn flatten_orphans() {
+        let mut investor = investor();
+        let (account, orders) = exchange(
+            100,
+            &[
+                holding(Coin::BTC, Decimal::new(25, 1)),
+                holding(Coin::DOGE, Decimal::new(1000, 0)),
+            ],
+        )
+        .await;
+
+        let mut reconciler = Reconciler::new(Policy::Flatten, Duration::minutes(5));
+        let mismatches = reconciler
+            .run(&prices(10, 20, Utc::now()), &account, &mut investor)
+            .await;
+        assert_eq!(mismatches.len(), 2);
+
+        let orders = orders.lock().unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0]["market"], "DOGE-PERP");
+        assert_eq!(orders[0]["side"], "sell");
+        assert_eq!(orders[0]["size"], 1000.0);
+        assert_eq!(orders[0]["reduceOnly"], true);
+    }
+
+    #[tokio::test]
+    async fn adopt() {
+        let mut investor = investor();
+        let (account, _) = exchange(120, &[holding(Coin::BTC, Decimal::new(45, 1))]).await;
+
+        let time = Utc::now();
+        let mut reconciler = Reconciler::new(Policy::Adopt, Duration::minutes(5));
+        let now = prices(10, 20, time);
+        assert_eq!(reconciler.run(&now, &account, &mut investor).await.len(), 3);
+        assert_eq!(investor.holdings()[&Coin::BTC], Decimal::new(45, 1));
+        assert_eq!(investor.holdings()[&Coin::ETH], Decimal::zero());
+        assert_eq!(investor.adopted()[0].price, Decimal::new(10, 0));
+        assert!(!reconciler.is_due(time + Duration::minutes(1)));
+        assert!(reconciler.is_due(time + Duration::minutes(5)));
+
+        // Adopted holdings don't pile up once in sync.
+        investor.update(&now);
+        let later = prices(10, 20, time + Duration::minutes(5));
+        assert!(reconciler
+            .run(&later, &account, &mut investor)
+            .await
+            .is_empty());
+        assert_eq!(investor.adopted().len(), 2);
+    }
+}
@@ -0,0 +1,109 @@
+use super::{Equity, Investor};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub profit: Decimal,
+    pub wins: usize,
+    pub losses: usize,
+    pub final_equity: Decimal,
+    pub max_drawdown: Decimal,
+}
+
+impl Report {
+    pub fn new(investor: &Investor) -> Self {
+        // The wallet may only keep the last equity, but tracks the drawdown of all.
+        Report {
+            max_drawdown: investor.wallet().max_drawdown(),
+            ..Self::with_equity(investor, investor.equity())
+        }
+    }
+
+    // Reports on the given equity instead of the one the investor marked, like that of all runs
+    // of a paper trader.
+    pub fn with_equity(investor: &Investor, equity: &[Equity]) -> Self {
+        let (wins, losses) = investor.wins_losses();
+
+        let mut peak = Decimal::zero();
+        let mut max_drawdown = Decimal::zero();
+        for equity in equity {
+            let total = equity.total();
+            peak = peak.max(total);
+            if peak > Decimal::zero() {
+                max_drawdown = max_drawdown.max((peak - total) / peak);
+            }
+        }
+
+        Report {
+            profit: investor.total_realized_profit(),
+            wins,
+            losses,
+            final_equity: equity
+                .last()
+                .map(Equity::total)
+                .unwrap_or_else(|| investor.total_equity()),
+            max_drawdown,
+        }
+    }
+
+    pub fn trades(&self) -> usize {
+        self.wins + self.losses
+    }
+
+    pub fn win_rate(&self) -> f32 {
+        if self.trades() == 0 {
+            0.0
+        } else {
+            self.wins as f32 / self.trades() as f32
+        }
+    }
+
+    pub fn log(&self) {
+        log::info!("TOTAL PROFIT: \t{:.2}", self.profit);
+        log::info!(
+            "EQUITY:       \t{:.2} (MAX DRAWDOWN {:.2}%)",
+            self.final_equity,
+            self.max_drawdown * Decimal::new(100, 0)
+        );
+        log::info!(
+            "WIN/LOSS:     \t{}/{} ({:.2}%)",
+            self.wins,
+            self.losses,
+            self.win_rate() * 100.0
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trader::{testing, Coin, Position};
+    use chrono::{Duration, Utc};
+
+    #[tokio::test]
+    async fn drawdown() {
+        let time = Utc::now();
+        let prices =
+            |btc, eth, minutes| testing::prices(btc, eth, time + Duration::minutes(minutes));
+
+        let mut investor = Investor::new();
+        investor.update(&prices(10, 10, 0));
+        investor
+            .open(
+                &prices(10, 10, 1),
+                Position::new(Coin::BTC, Coin::ETH, Decimal::new(5, 1)),
+            )
+            .await;
+        investor.update(&prices(5, 10, 2));
+        investor.close(&prices(20, 10, 3)).await;
+        investor.update(&prices(20, 10, 3));
+
+        let report = Report::new(&investor);
+        assert_eq!(report.wins, 1);
+        assert_eq!(report.losses, 0);
+        assert_eq!(report.profit, Decimal::new(25, 0));
+        assert_eq!(report.final_equity, Decimal::new(125, 0));
+        assert_eq!(report.max_drawdown, Decimal::new(125, 3));
+    }
+}
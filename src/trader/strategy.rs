@@ -1,169 +1,760 @@
-use super::{Candles, Candle, Coin, Position};
+use super::{Candle, Candles, Coin, Position};
 use crate::{
-    indicators::{Change, Corr, Cum, Ma, Stdev},
-    Number,
+    indicators::{
+        Chain, Change, CoMoment, Cum, Fork, Indicator, Last, Ma, Map, Real, Snapshot, Stdev,
+        Window, Zip,
+    },
+    journal::Event,
+    recorder::{Recorder, Series},
+    Number, JOURNAL,
+};
+use chrono::{DateTime, Utc};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashSet, VecDeque},
+    fs::{rename, File},
+    io::{self, BufReader},
+    path::Path,
 };
-use num_traits::Num;
-use rust_decimal::prelude::*;
-use crate::LOGGER;
 
-struct Pair {
-    corr: Corr,
-    stdev: Stdev,
-    count: usize,
-    out_diff: Number,
-    out_enter: bool,
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Params {
+    pub mov_period: usize,
+    pub corr_period: usize,
+    pub min_corr: Number,
+    pub stdev_mult: Number,
+    pub min_diff: Number,
+    pub max_diff: Number,
 }
 
-impl Pair {
-    pub fn new(corr_period: usize) -> Self {
-        Pair {
-            corr: Corr::new(corr_period),
-            stdev: Stdev::new(corr_period),
-            count: corr_period,
-            out_diff: 0.0,
-            out_enter: false,
+impl Params {
+    // Candles until the spread deviation covers full moving averages, to warm up on.
+    pub fn warmup(&self) -> usize {
+        self.mov_period + self.corr_period
+    }
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            mov_period: 60 * 60 * 24 / 15,
+            corr_period: 60 * 60 * 24 * 30 / 15,
+            min_corr: 0.95,
+            stdev_mult: 2.5,
+            min_diff: 0.05,
+            max_diff: 0.1,
         }
     }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Signal {
+    pub time: DateTime<Utc>,
+    pub long: Coin,
+    pub short: Coin,
+    pub corr: Number,
+    pub diff: Number,
+    pub stdev: Number,
+}
 
-    pub fn run(&mut self, long: &Single, short: &Single) {
-        let corr = self.corr.run(long.get_cum(), short.get_cum());
-        self.out_diff = short.get_mov() - long.get_mov();
-        let stdev = self.stdev.run(self.out_diff.abs());
+// Number of recent signals kept for inspection.
+const SIGNALS: usize = 100;
 
-        //LOGGER.pair(long.coin, short.coin).diff(self.out_diff);
+// Fewest pairs updated by one task, so small universes don't pay for the scheduling.
+const PAIRS_PER_TASK: usize = 32;
 
-        let max_diff = 0.1;
-        let min_diff = 0.05;
+type Unary<T> = fn(T) -> T;
+type Binary<T> = fn(T, T) -> T;
+type Mov<T> = Chain<Chain<Change<T>, Cum<T>>, Fork<Last<T>, Ma<T>, Binary<T>>>;
+type AbsStdev<T> = Chain<Map<Last<T>, Unary<T>>, Stdev<T>>;
 
-        if self.count > 0 {
-            // Backoff from trading for some time.
-            self.count -= 1;
+// Both directions of an unordered pair `a < b`, long `a` and short `b` first. The spread of
+// the reverse direction is negated, its correlation and deviation are the same.
+struct Pair<T: Real> {
+    a: usize,
+    b: usize,
+    // Correlation of the cumulated changes, from the windows kept by the legs.
+    co: CoMoment<T::Acc>,
+    // Deviation of the absolute spread.
+    stdev: AbsStdev<T>,
+    out_corr: T,
+    out_diff: T,
+    out_stdev: T,
+    out_enter: [bool; 2],
+    series: [Option<Series>; 2],
+}
+
+impl<T: Real> Pair<T> {
+    pub fn new(a: usize, b: usize, corr_period: usize) -> Self {
+        debug_assert!(a < b);
+
+        Pair {
+            a,
+            b,
+            co: CoMoment::new(),
+            stdev: Last::new()
+                .map(T::abs as Unary<T>)
+                .chain(Stdev::new(corr_period)),
+            out_corr: T::zero(),
+            out_diff: T::zero(),
+            out_stdev: T::zero(),
+            out_enter: [false; 2],
+            series: [None, None],
+        }
+    }
+
+    pub fn run(&mut self, singles: &[Single<T>], params: &Params) {
+        let (a, b) = (&singles[self.a], &singles[self.b]);
+        self.co.update(&a.cum, &b.cum);
+        self.out_diff = b.get_mov() - a.get_mov();
+        self.stdev.update(self.out_diff);
+        let corr = if a.cum.is_full() {
+            T::from_acc(self.co.corr(&a.cum, &b.cum))
         } else {
-            self.out_enter = corr > 0.95
-                && self.out_diff > stdev * 2.5
-                && max_diff >= self.out_diff
-                && self.out_diff >= min_diff
+            T::zero()
+        };
+        let stdev = self.stdev.value().unwrap_or_else(T::zero);
+        self.out_corr = corr;
+        self.out_stdev = stdev;
+
+        // Only trade once the correlation and spread windows are filled.
+        if a.cum.is_full() && self.stdev.is_ready() {
+            for direction in 0..2 {
+                let diff = self.get_diff(direction);
+                self.out_enter[direction] = corr > T::from_f64(params.min_corr as f64)
+                    && diff > stdev * T::from_f64(params.stdev_mult as f64)
+                    && T::from_f64(params.max_diff as f64) >= diff
+                    && diff >= T::from_f64(params.min_diff as f64)
                 //&& long.get_price_adj_ma() > long.get_price_ma()
                 //&& short.get_price_adj_ma() < short.get_price_ma();
+            }
+        }
+
+        for direction in 0..2 {
+            let values = [
+                corr.to_f64() as Number,
+                self.get_diff(direction).to_f64() as Number,
+                stdev.to_f64() as Number,
+                stdev.to_f64() as Number * params.stdev_mult,
+                self.out_enter[direction] as u8 as Number,
+            ];
+            if let Some(series) = &mut self.series[direction] {
+                series.record(a.get_time(), &values);
+            }
+        }
+    }
+
+    // Long and short leg of the direction.
+    pub fn legs(&self, direction: usize) -> (usize, usize) {
+        if direction == 0 {
+            (self.a, self.b)
+        } else {
+            (self.b, self.a)
         }
     }
 
-    pub fn should_enter(&self) -> bool {
-        self.out_enter
+    pub fn should_enter(&self, direction: usize) -> bool {
+        self.out_enter[direction]
     }
 
-    pub fn get_diff(&self) -> Number {
-        self.out_diff
+    pub fn get_diff(&self, direction: usize) -> T {
+        if direction == 0 {
+            self.out_diff
+        } else {
+            -self.out_diff
+        }
+    }
+
+    pub fn get_corr(&self) -> T {
+        self.out_corr
     }
+
+    pub fn get_stdev(&self) -> T {
+        self.out_stdev
+    }
+}
+
+// Position of the unordered pair of the given coin indices among all `n * (n - 1) / 2` pairs.
+fn pair_index(n: usize, a: usize, b: usize) -> usize {
+    let (a, b) = if a < b { (a, b) } else { (b, a) };
+    a * (2 * n - a - 1) / 2 + (b - a - 1)
 }
 
-pub struct Single {
+pub struct Single<T: Real = Number> {
     coin: Coin,
-    change: Change,
-    cum: Cum,
-    mean: Ma,
-    out_mov: Number,
-    out_cum: Number,
-    price: Number,
-    price_ma: Ma,
-    price_vol_ma: Ma,
-    vol_ma: Ma,
-    out_price_ma: Number,
-    out_price_adj_ma: Number,
-}
-
-impl Single {
-    pub fn new(coin: Coin, mov_period: usize) -> Self {
+    time: DateTime<Utc>,
+    // Cumulated relative price changes and their deviation from the moving average.
+    mov: Mov<T>,
+    // Window of the cumulated changes shared by all pairs of the coin.
+    cum: Window<T>,
+    price: T,
+    price_ma: Ma<T>,
+    // Volume weighted moving average of the price.
+    price_adj_ma: Zip<Ma<T>, Ma<T>, Binary<T>>,
+    series: Option<Series>,
+}
+
+impl<T: Real> Single<T> {
+    pub fn new(coin: Coin, mov_period: usize, corr_period: usize) -> Self {
         Single {
             coin,
-            change: Change::new(),
-            cum: Cum::new(),
-            mean: Ma::new(mov_period),
-            out_mov: 0.0,
-            out_cum: 0.0,
-            price: 0.0,
+            time: Utc::now(),
+            mov: Change::new()
+                .chain(Cum::new())
+                .chain(Last::new().fork(Ma::new(mov_period), |cum, mean| cum - mean)),
+            cum: Window::new(corr_period),
+            price: T::zero(),
             price_ma: Ma::new(100),
-            price_vol_ma: Ma::new(100),
-            vol_ma: Ma::new(100),
-            out_price_ma: 0.0,
-            out_price_adj_ma: 0.0,
+            price_adj_ma: Ma::new(100)
+                .zip(Ma::new(100), |price_volume, volume| price_volume / volume),
+            series: None,
         }
     }
 
     pub fn run(&mut self, candle: &Candle) {
-        let price = candle.close.to_f32().unwrap();
-        let volume = candle.volume.to_f32().unwrap();
-        self.out_price_ma = self.price_ma.run(price);
-        self.out_price_adj_ma = self.price_vol_ma.run(price * volume) / self.vol_ma.run(volume);
+        let price = T::from_decimal(candle.close);
+        let volume = T::from_decimal(candle.volume);
+        self.time = candle.time;
+        self.price = price;
+        self.price_ma.update(price);
+        self.price_adj_ma.update((price * volume, volume));
+        self.mov.update(price);
+        self.cum.push(self.get_cum());
+
+        let values = [
+            self.price.to_f64() as Number,
+            self.get_cum().to_f64() as Number,
+            self.get_mov().to_f64() as Number,
+            self.get_price_ma().to_f64() as Number,
+        ];
+        if let Some(series) = &mut self.series {
+            series.record(self.time, &values);
+        }
+    }
 
-        self.out_cum = self.cum.run(self.change.run(price));
-        let mean = self.mean.run(self.out_cum);
-        self.out_mov = self.out_cum - mean;
+    pub fn get_time(&self) -> DateTime<Utc> {
+        self.time
     }
 
-    pub fn get_price_ma(&self) -> Number {
-        self.out_price_ma
+    pub fn get_price_ma(&self) -> T {
+        self.price_ma.value().unwrap_or(self.price)
     }
 
-    pub fn get_price_adj_ma(&self) -> Number {
-        self.out_price_adj_ma
+    pub fn get_price_adj_ma(&self) -> T {
+        self.price_adj_ma.value().unwrap_or(self.price)
     }
 
-    pub fn get_cum(&self) -> Number {
-        self.out_cum
+    pub fn get_cum(&self) -> T {
+        self.mov.first().value().unwrap_or_else(T::zero)
     }
 
-    pub fn get_mov(&self) -> Number {
-        self.out_mov
+    pub fn get_mov(&self) -> T {
+        self.mov.value().unwrap_or_else(T::zero)
     }
 }
 
-pub struct Strategy {
-    singles: Vec<Single>,
-    pairs: Vec<Vec<Pair>>,
+// Indicators compute in `T`, `f64` or `Decimal` trade speed for precision.
+pub struct Strategy<T: Real = Number> {
+    singles: Vec<Single<T>>,
+    pairs: Vec<Pair<T>>,
+    params: Params,
+    signals: VecDeque<Signal>,
+    blacklist: HashSet<Coin>,
+    blacklist_pairs: HashSet<(Coin, Coin)>,
+    time: Option<DateTime<Utc>>,
 }
 
-impl Strategy {
-    pub fn new(coins: &[Coin]) -> Self {
+impl<T: Real> Strategy<T> {
+    pub fn new(coins: &[Coin], params: Params) -> Self {
+        Self::with_legs(&Coin::sorted(coins), params)
+    }
+
+    // One leg per given coin, without sorting or deduplicating them.
+    fn with_legs(coins: &[Coin], params: Params) -> Self {
         let mut singles = Vec::new();
         for &coin in coins {
-            singles.push(Single::new(coin, 60 * 60 * 24 / 15))
+            singles.push(Single::new(coin, params.mov_period, params.corr_period))
         }
 
         let mut pairs = Vec::new();
-        for _ in coins {
-            let mut p = Vec::new();
-            for _ in coins {
-                p.push(Pair::new(60 * 60 * 24 * 30 / 15))
+        for a in 0..coins.len() {
+            for b in a + 1..coins.len() {
+                pairs.push(Pair::new(a, b, params.corr_period));
             }
-            pairs.push(p);
         }
 
-        Strategy { singles, pairs }
+        Strategy {
+            singles,
+            pairs,
+            params,
+            signals: VecDeque::new(),
+            blacklist: HashSet::new(),
+            blacklist_pairs: HashSet::new(),
+            time: None,
+        }
+    }
+
+    // Records the indicators of the coins and pairs selected by the recorder.
+    pub fn record(&mut self, recorder: &Recorder) {
+        for single in &mut self.singles {
+            single.series = recorder.single(single.coin, &["price", "cum", "mov", "price_ma"]);
+        }
+
+        for pair in &mut self.pairs {
+            for direction in 0..2 {
+                let (long, short) = pair.legs(direction);
+                pair.series[direction] = recorder.pair_series(
+                    self.singles[long].coin,
+                    self.singles[short].coin,
+                    &["corr", "diff", "stdev", "threshold", "enter"],
+                );
+            }
+        }
+    }
+
+    pub fn flush(&mut self) {
+        let singles = self
+            .singles
+            .iter_mut()
+            .filter_map(|single| single.series.as_mut());
+        let pairs = self
+            .pairs
+            .iter_mut()
+            .flat_map(|pair| pair.series.iter_mut())
+            .flatten();
+        for series in singles.chain(pairs) {
+            series.flush();
+        }
+    }
+
+    // Blacklisted coins and pairs are still tracked, but never signalled.
+    pub fn blacklist(&mut self, coin: Coin, blacklisted: bool) {
+        if blacklisted {
+            self.blacklist.insert(coin);
+        } else {
+            self.blacklist.remove(&coin);
+        }
+    }
+
+    pub fn blacklist_pair(&mut self, long: Coin, short: Coin, blacklisted: bool) {
+        if blacklisted {
+            self.blacklist_pairs.insert((long, short));
+        } else {
+            self.blacklist_pairs.remove(&(long, short));
+        }
+    }
+
+    pub fn signals(&self) -> &VecDeque<Signal> {
+        &self.signals
     }
 
     pub fn run(&mut self, candles: &Candles) -> Option<Position> {
         //let prices_float: Vec<Number> = prices.iter().map(|d| d.close.to_f32().unwrap()).collect();
 
+        self.time = candles.first().map(|candle| candle.time);
         for (single, candle) in self.singles.iter_mut().zip(candles) {
             single.run(candle);
         }
 
-        for (p, long) in self.pairs.iter_mut().zip(self.singles.iter()) {
-            for (pair, short) in p.iter_mut().zip(self.singles.iter()) {
-                pair.run(long, short);
+        // Pairs only read the singles, so they are updated in parallel on the rayon pool.
+        let (singles, params) = (&self.singles, &self.params);
+        self.pairs
+            .par_iter_mut()
+            .with_min_len(PAIRS_PER_TASK)
+            .for_each(|pair| pair.run(singles, params));
 
-                if pair.should_enter() {
-                    return Some(Position::new(
-                        long.coin,
-                        short.coin,
-                        Decimal::from_f32(pair.get_diff()).unwrap(),
-                    ));
+        // Scan the directions in a fixed order, so the same candles always give the same signal.
+        let n = self.singles.len();
+        for (long, short) in (0..n).flat_map(|long| (0..n).map(move |short| (long, short))) {
+            if long == short {
+                continue;
+            }
+            let pair = &self.pairs[pair_index(n, long, short)];
+            let direction = (long > short) as usize;
+            let (long, short) = (&self.singles[long], &self.singles[short]);
+            let blacklisted = self.blacklist.contains(&long.coin)
+                || self.blacklist.contains(&short.coin)
+                || self.blacklist_pairs.contains(&(long.coin, short.coin));
+            if pair.should_enter(direction) && !blacklisted {
+                let signal = Signal {
+                    time: candles[0].time,
+                    long: long.coin,
+                    short: short.coin,
+                    corr: pair.get_corr().to_f64() as Number,
+                    diff: pair.get_diff(direction).to_f64() as Number,
+                    stdev: pair.get_stdev().to_f64() as Number,
+                };
+                JOURNAL.record(Event::Signal {
+                    time: signal.time,
+                    long: signal.long,
+                    short: signal.short,
+                    corr: signal.corr,
+                    diff: signal.diff,
+                    stdev: signal.stdev,
+                });
+                if self.signals.len() == SIGNALS {
+                    self.signals.pop_front();
                 }
+                self.signals.push_back(signal);
+                return Some(Position::new(
+                    long.coin,
+                    short.coin,
+                    pair.get_diff(direction).to_decimal().unwrap(),
+                ));
             }
         }
 
         None
     }
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+struct PairState<T: Real> {
+    co: CoMoment<T::Acc>,
+    stdev: <AbsStdev<T> as Snapshot>::State,
+    out_corr: T,
+    out_diff: T,
+    out_stdev: T,
+    out_enter: [bool; 2],
+}
+
+impl<T: Real> Snapshot for Pair<T> {
+    type State = PairState<T>;
+
+    fn snapshot(&self) -> PairState<T> {
+        PairState {
+            co: self.co,
+            stdev: self.stdev.snapshot(),
+            out_corr: self.out_corr,
+            out_diff: self.out_diff,
+            out_stdev: self.out_stdev,
+            out_enter: self.out_enter,
+        }
+    }
+
+    fn restore(&mut self, state: PairState<T>) {
+        self.co = state.co;
+        self.stdev.restore(state.stdev);
+        self.out_corr = state.out_corr;
+        self.out_diff = state.out_diff;
+        self.out_stdev = state.out_stdev;
+        self.out_enter = state.out_enter;
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct SingleState<T: Real> {
+    time: DateTime<Utc>,
+    mov: <Mov<T> as Snapshot>::State,
+    cum: Window<T>,
+    price: T,
+    price_ma: Ma<T>,
+    price_adj_ma: <Zip<Ma<T>, Ma<T>, Binary<T>> as Snapshot>::State,
+}
+
+impl<T: Real> Snapshot for Single<T> {
+    type State = SingleState<T>;
+
+    fn snapshot(&self) -> SingleState<T> {
+        SingleState {
+            time: self.time,
+            mov: self.mov.snapshot(),
+            cum: self.cum.clone(),
+            price: self.price,
+            price_ma: self.price_ma.clone(),
+            price_adj_ma: self.price_adj_ma.snapshot(),
+        }
+    }
+
+    fn restore(&mut self, state: SingleState<T>) {
+        self.time = state.time;
+        self.mov.restore(state.mov);
+        self.cum = state.cum;
+        self.price = state.price;
+        self.price_ma = state.price_ma;
+        self.price_adj_ma.restore(state.price_adj_ma);
+    }
+}
+
+// Indicator state of a strategy, to restart it without warming up its windows again.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct StrategyState<T: Real = Number> {
+    // Time of the last candle the strategy ran on.
+    pub time: Option<DateTime<Utc>>,
+    pub coins: Vec<Coin>,
+    pub params: Params,
+    singles: Vec<SingleState<T>>,
+    pairs: Vec<PairState<T>>,
+    signals: VecDeque<Signal>,
+    blacklist: HashSet<Coin>,
+    blacklist_pairs: HashSet<(Coin, Coin)>,
+}
+
+impl<T: Real> StrategyState<T> {
+    // Only restores state saved for the same coins and parameters.
+    pub fn load<P: AsRef<Path>>(path: P, coins: &[Coin], params: &Params) -> Option<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).ok()?;
+        let state: Self = match serde_cbor::from_reader(BufReader::new(file)) {
+            Ok(state) => state,
+            Err(err) => {
+                log::warn!("Could not read strategy snapshot {:?}: {}", path, err);
+                return None;
+            }
+        };
+        if state.coins != coins || state.params != *params {
+            log::warn!(
+                "Ignoring strategy snapshot {:?} of other coins or parameters.",
+                path
+            );
+            return None;
+        }
+        log::info!(
+            "Restoring strategy snapshot {:?} at {:?}.",
+            path,
+            state.time
+        );
+        Some(state)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        // Same as the paper trading state, never leave a partially written snapshot behind.
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        serde_cbor::to_writer(File::create(&tmp)?, self)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        rename(&tmp, path)
+    }
+}
+
+impl<T: Real> Snapshot for Strategy<T> {
+    type State = StrategyState<T>;
+
+    fn snapshot(&self) -> StrategyState<T> {
+        StrategyState {
+            time: self.time,
+            coins: self.singles.iter().map(|single| single.coin).collect(),
+            params: self.params,
+            singles: self.singles.iter().map(Snapshot::snapshot).collect(),
+            pairs: self.pairs.iter().map(Snapshot::snapshot).collect(),
+            signals: self.signals.clone(),
+            blacklist: self.blacklist.clone(),
+            blacklist_pairs: self.blacklist_pairs.clone(),
+        }
+    }
+
+    fn restore(&mut self, state: StrategyState<T>) {
+        assert_eq!(
+            state.pairs.len(),
+            self.pairs.len(),
+            "Snapshot of other coins."
+        );
+
+        self.time = state.time;
+        for (single, state) in self.singles.iter_mut().zip(state.singles) {
+            single.restore(state);
+        }
+        for (pair, state) in self.pairs.iter_mut().zip(state.pairs) {
+            pair.restore(state);
+        }
+        self.signals = state.signals;
+        self.blacklist = state.blacklist;
+        self.blacklist_pairs = state.blacklist_pairs;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trader::{tick, Investor, Market, Process, Scenario};
+    use chrono::Duration;
+    use futures::executor::block_on;
+    use rust_decimal::Decimal;
+    use std::time::Instant;
+
+    fn params() -> Params {
+        Params {
+            mov_period: 3,
+            corr_period: 5,
+            min_corr: -2.0,
+            stdev_mult: 0.5,
+            min_diff: 0.0,
+            max_diff: 1.0,
+        }
+    }
+
+    fn candles() -> Vec<Candles> {
+        let time = Utc::now();
+        (0..50i64)
+            .map(|i| {
+                let candle = |coin, close| Candle {
+                    coin,
+                    close: Decimal::new(close, 2),
+                    volume: Decimal::ONE,
+                    time: time + Duration::seconds(15 * i),
+                };
+                vec![
+                    candle(Coin::BTC, 10_000 + i * 7 % 13 * 50),
+                    candle(Coin::ETH, 20_000 + i * 5 % 11 * 80),
+                    candle(Coin::SOL, 5_000 + i * 3 % 7 * 40),
+                ]
+            })
+            .collect()
+    }
+
+    fn run<T: Real>(
+        strategy: &mut Strategy<T>,
+        candles: &[Candles],
+    ) -> Vec<Option<(Coin, Coin, Decimal)>> {
+        candles
+            .iter()
+            .map(|prices| {
+                strategy
+                    .run(prices)
+                    .map(|p| (p.long, p.short, p.take_profit.round_dp(4)))
+            })
+            .collect()
+    }
+
+    fn positions<T: Real>(candles: &[Candles]) -> Vec<Option<(Coin, Coin, Decimal)>> {
+        let mut strategy = Strategy::<T>::new(&[Coin::BTC, Coin::ETH, Coin::SOL], params());
+        run(&mut strategy, candles)
+    }
+
+    #[test]
+    fn precision() {
+        let candles = candles();
+        let double = positions::<f64>(&candles);
+        assert!(double.iter().any(Option::is_some));
+        assert_eq!(double, positions::<Decimal>(&candles));
+
+        // Signals don't depend on how the pairs are scheduled.
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        assert_eq!(double, pool.install(|| positions::<f64>(&candles)));
+    }
+
+    #[test]
+    fn snapshot() {
+        let coins = [Coin::BTC, Coin::ETH, Coin::SOL];
+        let candles = candles();
+        let (before, after) = candles.split_at(20);
+        let path = std::env::temp_dir().join(format!("strategy-{}.cbor", rand::random::<u64>()));
+
+        let mut strategy = Strategy::<Number>::new(&coins, params());
+        run(&mut strategy, before);
+        strategy.snapshot().save(&path).unwrap();
+        assert!(StrategyState::<Number>::load(&path, &coins[1..], &params()).is_none());
+
+        let state = StrategyState::<Number>::load(&path, &coins, &params()).unwrap();
+        assert_eq!(state.time, Some(before.last().unwrap()[0].time));
+        let mut restored = Strategy::new(&coins, params());
+        restored.restore(state);
+        assert_eq!(run(&mut strategy, after), run(&mut restored, after));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn pairs() {
+        let n = 7;
+        let mut indices: Vec<usize> = (0..n)
+            .flat_map(|a| (a + 1..n).map(move |b| pair_index(n, a, b)))
+            .collect();
+        assert_eq!(pair_index(n, 4, 2), pair_index(n, 2, 4));
+        indices.sort_unstable();
+        assert_eq!(indices, (0..n * (n - 1) / 2).collect::<Vec<_>>());
+
+        let strategy = Strategy::<Number>::new(&Coin::all(), Params::default());
+        assert_eq!(strategy.pairs.len(), 21 * 20 / 2);
+    }
+
+    // Run with `cargo test --release benchmark -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn benchmark() {
+        // Short windows, filled after the warm-up, and thresholds most spreads pass, so every
+        // tick computes correlations and the investor opens and closes positions.
+        let params = Params {
+            mov_period: 20,
+            corr_period: 240,
+            min_corr: 0.0,
+            stdev_mult: 0.5,
+            min_diff: 0.0,
+            max_diff: 1.0,
+        };
+        let ticks = 1000;
+        for &n in &[21, 100] {
+            // More legs than there are coins share them, in the order candles are kept in.
+            let mut legs: Vec<Coin> = (0..n).map(|j| Coin::all()[j % 21]).collect();
+            legs.sort();
+            let mut strategy = Strategy::<Number>::with_legs(&legs, params);
+            assert_eq!(strategy.singles.len(), n);
+            assert_eq!(strategy.pairs.len(), n * (n - 1) / 2);
+
+            // All legs follow the first one with mean reverting spreads.
+            let markets = legs
+                .iter()
+                .enumerate()
+                .map(|(j, &coin)| Market {
+                    coin,
+                    price: Decimal::new(100 + j as i64, 0),
+                    volume: Decimal::ONE,
+                    process: if j == 0 {
+                        Process::Gbm {
+                            drift: 0.0,
+                            volatility: 0.001,
+                            correlation: 0.0,
+                        }
+                    } else {
+                        Process::Cointegrated {
+                            with: legs[0],
+                            beta: 1.0,
+                            half_life: 20.0,
+                            volatility: 0.002,
+                        }
+                    },
+                })
+                .collect();
+            let scenario = Scenario {
+                candles: params.warmup() + ticks,
+                markets,
+                ..Scenario::default()
+            };
+            let candles = scenario
+                .generate(Utc::now(), Duration::seconds(15))
+                .unwrap();
+            let (warmup, candles) = candles.split_at(params.warmup());
+            for prices in warmup {
+                strategy.run(prices);
+            }
+
+            let mut investor = Investor::new();
+            let mut signals = 0;
+            let start = Instant::now();
+            for prices in candles {
+                let signal = strategy.run(prices);
+                signals += signal.is_some() as usize;
+                block_on(tick(&mut investor, prices, signal));
+            }
+            let elapsed = start.elapsed();
+            assert!(signals > 0);
+
+            println!(
+                "{} markets, {} pairs, {} threads: {:?} per tick, {} signals, {} closed positions",
+                n,
+                strategy.pairs.len(),
+                rayon::current_num_threads(),
+                elapsed / ticks as u32,
+                signals,
+                investor.wins_losses().0 + investor.wins_losses().1
+            );
+        }
+    }
+}
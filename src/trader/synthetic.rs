@@ -0,0 +1,435 @@
+use super::{Candle, Candles, Coin};
+use chrono::{DateTime, Duration, Utc};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{f64::consts::PI, fs::read_to_string, path::Path};
+
+// Process driving the log price of a coin, with drift and volatility per candle.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "process", rename_all = "snake_case")]
+pub enum Process {
+    // Geometric Brownian motion, correlated with all others through a common market factor.
+    Gbm {
+        drift: f64,
+        volatility: f64,
+        correlation: f64,
+    },
+    // Follows `beta` times the log returns of an earlier coin, plus a spread that reverts to
+    // zero with the given half-life in candles.
+    Cointegrated {
+        with: Coin,
+        beta: f64,
+        half_life: f64,
+        volatility: f64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Market {
+    pub coin: Coin,
+    pub price: Decimal,
+    pub volume: Decimal,
+    #[serde(flatten)]
+    pub process: Process,
+}
+
+// From the given candle on, scales the volatility and adds to the drift of all coins.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Regime {
+    pub start: usize,
+    pub volatility: f64,
+    pub drift: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shock", rename_all = "snake_case")]
+pub enum Shock {
+    // Price drops by the given fraction and recovers linearly over the given candles.
+    FlashCrash {
+        coin: Coin,
+        at: usize,
+        drop: f64,
+        recovery: usize,
+    },
+    // No data for the given candles, filled with the last candle as the fetcher does.
+    Gap {
+        coin: Coin,
+        at: usize,
+        length: usize,
+    },
+}
+
+// Multi-coin candles with known properties, to test against without the API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub seed: u64,
+    pub candles: usize,
+    pub markets: Vec<Market>,
+    pub regimes: Vec<Regime>,
+    pub shocks: Vec<Shock>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            seed: 0,
+            candles: 60 * 60 * 24 / 15,
+            markets: Vec::new(),
+            regimes: Vec::new(),
+            shocks: Vec::new(),
+        }
+    }
+}
+
+impl Scenario {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let toml = read_to_string(path).map_err(|err| format!("{:?}: {}", path, err))?;
+        let scenario: Scenario =
+            toml::from_str(&toml).map_err(|err| format!("{:?}: {}", path, err))?;
+        scenario
+            .validate()
+            .map_err(|err| format!("{:?}: {}", path, err))?;
+        Ok(scenario)
+    }
+
+    // Fails on markets that can't be generated, with a price that isn't positive or following
+    // a coin listed after them.
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, market) in self.markets.iter().enumerate() {
+            if market.price <= Decimal::zero() {
+                return Err(format!("{} has to start at a positive price", market.coin));
+            }
+            if let Process::Cointegrated { with, .. } = market.process {
+                self.index(with, i)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Fails with the coins no market is defined for.
+    pub fn check(&self, coins: &[Coin]) -> Result<(), String> {
+        self.validate()?;
+        let missing: Vec<String> = coins
+            .iter()
+            .filter(|&&coin| self.markets.iter().all(|market| market.coin != coin))
+            .map(Coin::to_string)
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "no market for {}, add it to the scenario or leave it out of --coins",
+                missing.join(", ")
+            ))
+        }
+    }
+
+    // Fails on invalid markets, or once a price gets too large to be a decimal.
+    pub fn generate(
+        &self,
+        from: DateTime<Utc>,
+        interval: Duration,
+    ) -> Result<Vec<Candles>, String> {
+        self.validate()?;
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let start: Vec<f64> = self
+            .markets
+            .iter()
+            .map(|market| market.price.to_f64().unwrap().ln())
+            .collect();
+        let mut log_prices = start.clone();
+        let mut spreads = vec![0.0; self.markets.len()];
+        let mut history: Vec<Candles> = Vec::with_capacity(self.candles);
+
+        for t in 0..self.candles {
+            let (volatility, drift) = self
+                .regimes
+                .iter()
+                .rev()
+                .find(|regime| regime.start <= t)
+                .map_or((1.0, 0.0), |regime| (regime.volatility, regime.drift));
+            let common = normal(&mut rng);
+            let time = from + interval * t as i32;
+
+            let mut prices = Vec::with_capacity(self.markets.len());
+            for (i, market) in self.markets.iter().enumerate() {
+                let z = normal(&mut rng);
+                if t > 0 {
+                    match market.process {
+                        Process::Gbm {
+                            drift: mu,
+                            volatility: sigma,
+                            correlation,
+                        } => {
+                            let sigma = sigma * volatility;
+                            let z = correlation.sqrt() * common + (1.0 - correlation).sqrt() * z;
+                            log_prices[i] += mu + drift - sigma * sigma / 2.0 + sigma * z;
+                        }
+                        Process::Cointegrated {
+                            with,
+                            beta,
+                            half_life,
+                            volatility: sigma,
+                        } => {
+                            let j = self.index(with, i)?;
+                            spreads[i] =
+                                0.5f64.powf(1.0 / half_life) * spreads[i] + sigma * volatility * z;
+                            log_prices[i] =
+                                start[i] + beta * (log_prices[j] - start[j]) + spreads[i];
+                        }
+                    }
+                }
+
+                let mut price = log_prices[i].exp() * self.crash(market.coin, t);
+                let mut volume = market.volume;
+                if self.is_gap(market.coin, t) {
+                    if let Some(last) = history.last() {
+                        price = last[i].close.to_f64().unwrap();
+                        volume = last[i].volume;
+                    }
+                }
+                let close = Decimal::from_f64(price).ok_or_else(|| {
+                    format!(
+                        "{} reached the price {} at candle {}",
+                        market.coin, price, t
+                    )
+                })?;
+                prices.push(Candle {
+                    coin: market.coin,
+                    close: close.round_dp(8),
+                    volume,
+                    time,
+                });
+            }
+            history.push(prices);
+        }
+        Ok(history)
+    }
+
+    // Index of a coin the market at index `i` depends on, which has to be generated before it.
+    fn index(&self, coin: Coin, i: usize) -> Result<usize, String> {
+        self.markets[..i]
+            .iter()
+            .position(|market| market.coin == coin)
+            .ok_or_else(|| {
+                format!(
+                    "{} follows {}, which has to be listed before it",
+                    self.markets[i].coin, coin
+                )
+            })
+    }
+
+    fn crash(&self, coin: Coin, t: usize) -> f64 {
+        self.shocks.iter().fold(1.0, |factor, shock| match *shock {
+            Shock::FlashCrash {
+                coin: c,
+                at,
+                drop,
+                recovery,
+            } if c == coin && at <= t && t <= at + recovery => {
+                let recovered = if recovery == 0 {
+                    0.0
+                } else {
+                    (t - at) as f64 / recovery as f64
+                };
+                factor * (1.0 - drop * (1.0 - recovered))
+            }
+            _ => factor,
+        })
+    }
+
+    fn is_gap(&self, coin: Coin, t: usize) -> bool {
+        self.shocks.iter().any(|shock| match *shock {
+            Shock::Gap {
+                coin: c,
+                at,
+                length,
+            } => c == coin && at <= t && t < at + length,
+            _ => false,
+        })
+    }
+}
+
+// Standard normal sample, with the Box-Muller transform.
+fn normal(rng: &mut StdRng) -> f64 {
+    let u: f64 = 1.0 - rng.gen::<f64>();
+    let v: f64 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        indicators::{Corr, Indicator},
+        trader::{lazy_rest, ByCoin, Fetcher},
+    };
+    use chrono::TimeZone;
+
+    fn scenario() -> Scenario {
+        let gbm = |coin, correlation| Market {
+            coin,
+            price: Decimal::new(100, 0),
+            volume: Decimal::new(10, 0),
+            process: Process::Gbm {
+                drift: 0.0,
+                volatility: 0.001,
+                correlation,
+            },
+        };
+        Scenario {
+            seed: 7,
+            candles: 5000,
+            markets: vec![
+                gbm(Coin::BTC, 0.81),
+                gbm(Coin::ETH, 0.81),
+                Market {
+                    coin: Coin::SOL,
+                    price: Decimal::new(20, 0),
+                    volume: Decimal::new(10, 0),
+                    process: Process::Cointegrated {
+                        with: Coin::BTC,
+                        beta: 1.0,
+                        half_life: 50.0,
+                        volatility: 0.002,
+                    },
+                },
+            ],
+            regimes: vec![Regime {
+                start: 4000,
+                volatility: 3.0,
+                drift: 0.0,
+            }],
+            shocks: vec![
+                Shock::FlashCrash {
+                    coin: Coin::ETH,
+                    at: 1000,
+                    drop: 0.2,
+                    recovery: 10,
+                },
+                Shock::Gap {
+                    coin: Coin::BTC,
+                    at: 2000,
+                    length: 5,
+                },
+            ],
+        }
+    }
+
+    fn log_returns(history: &[Candles], coin: Coin, range: std::ops::Range<usize>) -> Vec<f64> {
+        history[range]
+            .windows(2)
+            .map(|w| {
+                (w[1].coin(coin).close / w[0].coin(coin).close)
+                    .to_f64()
+                    .unwrap()
+                    .ln()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn known() {
+        let scenario = scenario();
+        let history = scenario
+            .generate(Utc::now(), Duration::seconds(15))
+            .unwrap();
+        assert_eq!(history.len(), 5000);
+        assert_eq!(
+            history,
+            scenario
+                .generate(history[0][0].time, Duration::seconds(15))
+                .unwrap()
+        );
+
+        // Both follow the common factor with a correlation of 0.9, so 0.81 with each other.
+        let mut corr = Corr::<f64>::new(900);
+        let btc = log_returns(&history, Coin::BTC, 3000..3901);
+        let eth = log_returns(&history, Coin::ETH, 3000..3901);
+        for (&x, &y) in btc.iter().zip(&eth) {
+            corr.update((x, y));
+        }
+        assert!((corr.value().unwrap() - 0.81).abs() < 0.05);
+
+        // Higher volatility in the second regime.
+        let deviation = |returns: Vec<f64>| returns.iter().map(|r| r * r).sum::<f64>().sqrt();
+        let calm = deviation(log_returns(&history, Coin::BTC, 3000..3901));
+        let wild = deviation(log_returns(&history, Coin::BTC, 4000..4901));
+        assert!(wild > 2.0 * calm);
+
+        // The spread reverts with its half-life, estimated from its autocorrelation.
+        let spread: Vec<f64> = history
+            .iter()
+            .map(|prices| {
+                (prices.coin(Coin::SOL).close / Decimal::new(20, 0))
+                    .to_f64()
+                    .unwrap()
+                    .ln()
+                    - (prices.coin(Coin::BTC).close / Decimal::new(100, 0))
+                        .to_f64()
+                        .unwrap()
+                        .ln()
+            })
+            .filter(|s| s.is_finite())
+            .collect();
+        let (xy, xx) = spread.windows(2).fold((0.0, 0.0), |(xy, xx), w| {
+            (xy + w[0] * w[1], xx + w[0] * w[0])
+        });
+        let half_life = 0.5f64.ln() / (xy / xx).ln();
+        assert!((30.0..80.0).contains(&half_life), "{}", half_life);
+
+        let eth = |t: usize| history[t].coin(Coin::ETH).close;
+        assert!(eth(1000) < eth(999) * Decimal::new(85, 2));
+        assert!(eth(1011) > eth(999) * Decimal::new(95, 2));
+        let btc = |t: usize| *history[t].coin(Coin::BTC);
+        assert_eq!(btc(2003).close, btc(1999).close);
+        assert_ne!(btc(2005).close, btc(2004).close);
+    }
+
+    #[tokio::test]
+    async fn source() {
+        let scenario = scenario();
+        let from = Utc.ymd(2021, 1, 1).and_hms(0, 0, 0);
+        let interval = Duration::seconds(15);
+        let mut fetcher = Fetcher::new(&[Coin::SOL, Coin::BTC], from, interval)
+            .until(from + interval * 100)
+            .synthetic(&scenario)
+            .unwrap();
+        // Never built, as the candles are generated.
+        let rest = lazy_rest();
+
+        let prices = fetcher.next(&rest).await.unwrap();
+        assert_eq!(prices[0].coin, Coin::BTC);
+        assert_eq!(prices[1].coin, Coin::SOL);
+        assert_eq!(fetcher.history(&rest).await.len(), 99);
+    }
+
+    #[test]
+    fn check() {
+        let scenario = scenario();
+        assert!(scenario.check(&[Coin::BTC, Coin::SOL]).is_ok());
+        let err = scenario.check(&[Coin::BTC, Coin::DOGE]).unwrap_err();
+        assert!(err.contains("no market for DOGE"), "{}", err);
+
+        // A coin following one listed after it.
+        let mut reordered = scenario.clone();
+        reordered.markets.rotate_left(2);
+        let err = reordered.check(&[Coin::BTC]).unwrap_err();
+        assert!(err.contains("SOL-PERP follows BTC-PERP"), "{}", err);
+
+        // Prices that grow too large to be decimals.
+        let mut explosive = scenario;
+        explosive.markets[0].process = Process::Gbm {
+            drift: 10.0,
+            volatility: 0.0,
+            correlation: 0.0,
+        };
+        let err = explosive
+            .generate(Utc::now(), Duration::seconds(15))
+            .unwrap_err();
+        assert!(err.contains("BTC-PERP reached the price"), "{}", err);
+    }
+}
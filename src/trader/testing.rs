@@ -0,0 +1,74 @@
+// Candles and a mock exchange shared by the tests.
+use super::{Candle, Coin, Exchange, Holding};
+use chrono::{DateTime, Utc};
+use hyper::{
+    body::to_bytes,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server,
+};
+use rust_decimal::prelude::*;
+use serde_json::{json, Value};
+use std::{
+    convert::Infallible,
+    sync::{Arc, Mutex},
+};
+
+pub fn candle(coin: Coin, close: i64, time: DateTime<Utc>) -> Candle {
+    Candle {
+        coin,
+        close: Decimal::new(close, 0),
+        volume: Decimal::zero(),
+        time,
+    }
+}
+
+pub fn prices(btc: i64, eth: i64, time: DateTime<Utc>) -> Vec<Candle> {
+    vec![candle(Coin::BTC, btc, time), candle(Coin::ETH, eth, time)]
+}
+
+// Serves the account endpoints of the exchange on a local port, recording the placed orders.
+pub async fn exchange(balance: i64, holdings: &[Holding]) -> (Exchange, Arc<Mutex<Vec<Value>>>) {
+    let account = json!({"success": true, "result": {"totalAccountValue": balance}});
+    let positions: Vec<Value> = holdings
+        .iter()
+        .map(
+            |holding| json!({"future": holding.coin.to_string(), "netSize": holding.size.to_f64()}),
+        )
+        .collect();
+    let positions = json!({"success": true, "result": positions});
+    let orders = Arc::new(Mutex::new(Vec::new()));
+
+    let placed = orders.clone();
+    let service = make_service_fn(move |_| {
+        let (account, positions, orders) = (account.clone(), positions.clone(), orders.clone());
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                let (account, positions, orders) =
+                    (account.clone(), positions.clone(), orders.clone());
+                async move {
+                    assert!(req.headers().contains_key("FTX-SIGN"));
+                    let response = match (req.method(), req.uri().path()) {
+                        (&Method::GET, "/api/account") => account,
+                        (&Method::GET, "/api/positions") => positions,
+                        (&Method::POST, "/api/orders") => {
+                            let body = to_bytes(req.into_body()).await.unwrap();
+                            orders
+                                .lock()
+                                .unwrap()
+                                .push(serde_json::from_slice(&body).unwrap());
+                            json!({"success": true, "result": {"avgFillPrice": null}})
+                        }
+                        _ => json!({"success": false, "error": "Not found"}),
+                    };
+                    Ok::<_, Infallible>(Response::new(Body::from(response.to_string())))
+                }
+            }))
+        }
+    });
+    let server = Server::try_bind(&([127, 0, 0, 1], 0).into())
+        .unwrap()
+        .serve(service);
+    let endpoint = format!("http://{}/api", server.local_addr());
+    tokio::spawn(server);
+    (Exchange::new(&endpoint, "key", "secret", None), placed)
+}
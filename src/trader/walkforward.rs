@@ -0,0 +1,340 @@
+use super::{simulate, warmup, Candles, Equity, Optimizer, Params, Report, Search};
+use crate::{indicators::Real, journal::silently, Number};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::prelude::*;
+use std::{fs::File, io, io::Write, ops::Range, path::Path};
+
+pub struct WalkForward {
+    in_sample: Duration,
+    out_of_sample: Duration,
+}
+
+pub struct Window {
+    pub from: DateTime<Utc>,
+    pub split: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub params: Params,
+    pub in_sample: Report,
+    pub out_of_sample: Report,
+}
+
+pub struct Validation {
+    pub windows: Vec<Window>,
+    // Out-of-sample equity of all windows, each window continuing where the last one ended.
+    pub equity: Vec<Equity>,
+}
+
+impl WalkForward {
+    pub fn new(in_sample: Duration, out_of_sample: Duration) -> Self {
+        assert!(in_sample > Duration::zero());
+        assert!(out_of_sample > Duration::zero());
+
+        WalkForward {
+            in_sample,
+            out_of_sample,
+        }
+    }
+
+    // Splits the history into rolling in-sample and out-of-sample index ranges.
+    pub fn windows(&self, history: &[Candles]) -> Vec<(Range<usize>, Range<usize>)> {
+        let index = |time: DateTime<Utc>| history.partition_point(|prices| prices[0].time < time);
+
+        let mut windows = Vec::new();
+        let mut start = 0;
+        while start < history.len() {
+            let from = history[start][0].time;
+            let split = index(from + self.in_sample);
+            if split >= history.len() {
+                break;
+            }
+            let end = index(history[split][0].time + self.out_of_sample);
+            windows.push((start..split, split..end));
+            start = index(from + self.out_of_sample);
+        }
+        windows
+    }
+
+    pub async fn run<T: Real>(&self, optimizer: &Optimizer<T>, search: &Search) -> Validation {
+        let history = optimizer.history();
+        let candidates = search.candidates();
+        // Both segments of a window warm up on the candles before them, so the first window starts
+        // after the longest warm-up of all candidates.
+        let offset = candidates
+            .iter()
+            .map(Params::warmup)
+            .max()
+            .unwrap_or(0)
+            .min(history.len());
+        let windows: Vec<(Range<usize>, Range<usize>)> = self
+            .windows(&history[offset..])
+            .into_iter()
+            .map(|(in_sample, out_of_sample)| {
+                (
+                    in_sample.start + offset..in_sample.end + offset,
+                    out_of_sample.start + offset..out_of_sample.end + offset,
+                )
+            })
+            .collect();
+
+        let mut validation = Validation {
+            windows: Vec::new(),
+            equity: Vec::new(),
+        };
+
+        for (i, (in_sample, out_of_sample)) in windows.iter().cloned().enumerate() {
+            log::info!(
+                "Walk-forward window {}/{}: in-sample from {}, out-of-sample from {} to {}.",
+                i + 1,
+                windows.len(),
+                history[in_sample.start][0].time,
+                history[out_of_sample.start][0].time,
+                history[out_of_sample.end - 1][0].time
+            );
+
+            let results = optimizer
+                .run_range(candidates.clone(), in_sample.clone())
+                .await;
+            let (params, in_sample_report) = match results.first() {
+                Some(&result) => result,
+                None => {
+                    log::warn!("No parameters to search, skipping window {}.", i + 1);
+                    continue;
+                }
+            };
+
+            let investor = silently(simulate::<T>(
+                optimizer.coins(),
+                params,
+                optimizer.costs(),
+                &history[warmup(&out_of_sample, &params)],
+                &history[out_of_sample.clone()],
+            ))
+            .await;
+
+            validation.stitch(investor.equity());
+            validation.windows.push(Window {
+                from: history[in_sample.start][0].time,
+                split: history[out_of_sample.start][0].time,
+                to: history[out_of_sample.end - 1][0].time,
+                params,
+                in_sample: in_sample_report,
+                out_of_sample: Report::new(&investor),
+            });
+        }
+
+        validation
+    }
+}
+
+impl Validation {
+    fn stitch(&mut self, equity: &[Equity]) {
+        let scale = match (self.equity.last(), equity.first()) {
+            (Some(last), Some(first)) if !first.total().is_zero() => last.total() / first.total(),
+            _ => Decimal::one(),
+        };
+
+        self.equity.extend(equity.iter().map(|equity| Equity {
+            time: equity.time,
+            cash: equity.cash * scale,
+            exposure: equity.exposure * scale,
+            unrealized_profit: equity.unrealized_profit * scale,
+        }));
+    }
+
+    // Mean and standard deviation of each chosen parameter across all windows.
+    pub fn stability(&self) -> Vec<(&'static str, Number, Number)> {
+        let stats = |name, values: Vec<Number>| {
+            let n = values.len().max(1) as Number;
+            let mean = values.iter().sum::<Number>() / n;
+            let var = values.iter().map(|v| (v - mean).powi(2)).sum::<Number>() / n;
+            (name, mean, var.sqrt())
+        };
+        let params = |f: fn(&Params) -> Number| -> Vec<Number> {
+            self.windows
+                .iter()
+                .map(|window| f(&window.params))
+                .collect()
+        };
+
+        vec![
+            stats("mov_period", params(|p| p.mov_period as Number)),
+            stats("corr_period", params(|p| p.corr_period as Number)),
+            stats("min_corr", params(|p| p.min_corr)),
+            stats("stdev_mult", params(|p| p.stdev_mult)),
+            stats("min_diff", params(|p| p.min_diff)),
+            stats("max_diff", params(|p| p.max_diff)),
+        ]
+    }
+
+    pub fn log(&self) {
+        for window in &self.windows {
+            log::info!(
+                "WINDOW {} \tIN-SAMPLE PROFIT = {:.2} \tOUT-OF-SAMPLE PROFIT = {:.2} \t{:?}",
+                window.split,
+                window.in_sample.profit,
+                window.out_of_sample.profit,
+                window.params
+            );
+        }
+        for (name, mean, stdev) in self.stability() {
+            log::info!("{:12}\tMEAN = {:.4} \tSTDEV = {:.4}", name, mean, stdev);
+        }
+        if let Some(equity) = self.equity.last() {
+            log::info!("OUT-OF-SAMPLE EQUITY: \t{:.2}", equity.total());
+        }
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, dir: P) -> io::Result<()> {
+        let mut file = File::create(dir.as_ref().join("walkforward.csv"))?;
+        writeln!(
+            file,
+            "from,split,to,mov_period,corr_period,min_corr,stdev_mult,min_diff,max_diff,in_sample_profit,out_of_sample_profit,out_of_sample_max_drawdown"
+        )?;
+        for window in &self.windows {
+            let params = &window.params;
+            writeln!(
+                file,
+                "{},{},{},{},{},{},{},{},{},{},{},{}",
+                window.from,
+                window.split,
+                window.to,
+                params.mov_period,
+                params.corr_period,
+                params.min_corr,
+                params.stdev_mult,
+                params.min_diff,
+                params.max_diff,
+                window.in_sample.profit,
+                window.out_of_sample.profit,
+                window.out_of_sample.max_drawdown
+            )?;
+        }
+
+        let mut file = File::create(dir.as_ref().join("walkforward_equity.csv"))?;
+        writeln!(file, "time,total,cash,exposure")?;
+        for equity in &self.equity {
+            writeln!(
+                file,
+                "{},{},{},{}",
+                equity.time,
+                equity.total(),
+                equity.cash,
+                equity.exposure
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trader::{Candle, Coin, Costs, Grid};
+    use std::sync::Arc;
+
+    #[test]
+    fn windows() {
+        let from = Utc::now();
+        let history: Vec<Candles> = (0..100)
+            .map(|i| {
+                vec![Candle {
+                    coin: Coin::BTC,
+                    close: Decimal::one(),
+                    volume: Decimal::zero(),
+                    time: from + Duration::minutes(i),
+                }]
+            })
+            .collect();
+
+        let walk_forward = WalkForward::new(Duration::minutes(30), Duration::minutes(20));
+        assert_eq!(
+            walk_forward.windows(&history),
+            vec![
+                (0..30, 30..50),
+                (20..50, 50..70),
+                (40..70, 70..90),
+                (60..90, 90..100),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn empty() {
+        let from = Utc::now();
+        let history: Vec<Candles> = (0..100)
+            .map(|i| {
+                vec![Candle {
+                    coin: Coin::BTC,
+                    close: Decimal::one(),
+                    volume: Decimal::zero(),
+                    time: from + Duration::minutes(i),
+                }]
+            })
+            .collect();
+        let optimizer = Optimizer::<Number>::new(&[Coin::BTC], Costs::zero(), Arc::new(history));
+        let search = Search::Grid(Grid {
+            mov_period: Vec::new(),
+            ..Grid::default()
+        });
+
+        let walk_forward = WalkForward::new(Duration::minutes(30), Duration::minutes(20));
+        let validation = walk_forward.run(&optimizer, &search).await;
+        assert!(validation.windows.is_empty());
+        assert!(validation.equity.is_empty());
+    }
+
+    #[tokio::test]
+    async fn warm_up() {
+        let from = Utc::now();
+        let history: Vec<Candles> = (0..100)
+            .map(|i| {
+                vec![Candle {
+                    coin: Coin::BTC,
+                    close: Decimal::one(),
+                    volume: Decimal::zero(),
+                    time: from + Duration::minutes(i),
+                }]
+            })
+            .collect();
+        let optimizer = Optimizer::<Number>::new(&[Coin::BTC], Costs::zero(), Arc::new(history));
+        let search = Search::Grid(Grid {
+            mov_period: vec![2, 4],
+            corr_period: vec![6],
+            min_corr: vec![0.9],
+            stdev_mult: vec![2.0],
+            min_diff: vec![0.05],
+            max_diff: vec![0.1],
+        });
+
+        // In-sample segments start after the longest warm-up of 10 candles, like the
+        // out-of-sample ones after theirs.
+        let walk_forward = WalkForward::new(Duration::minutes(30), Duration::minutes(20));
+        let validation = walk_forward.run(&optimizer, &search).await;
+        assert_eq!(validation.windows.len(), 3);
+        assert_eq!(validation.windows[0].from, from + Duration::minutes(10));
+        assert_eq!(validation.windows[0].split, from + Duration::minutes(40));
+        assert_eq!(warmup(&(40..60), &validation.windows[0].params), 32..40);
+    }
+
+    #[test]
+    fn stitch() {
+        let time = Utc::now();
+        let equity = |total| Equity {
+            time,
+            cash: Decimal::new(total, 0),
+            exposure: Decimal::zero(),
+            unrealized_profit: Decimal::zero(),
+        };
+
+        let mut validation = Validation {
+            windows: Vec::new(),
+            equity: Vec::new(),
+        };
+        validation.stitch(&[equity(100), equity(110)]);
+        validation.stitch(&[equity(100), equity(90)]);
+        assert_eq!(
+            validation.equity.last().unwrap().total(),
+            Decimal::new(99, 0)
+        );
+    }
+}
@@ -1,9 +1,35 @@
+use chrono::{DateTime, Utc};
 use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Equity {
+    pub time: DateTime<Utc>,
+    pub cash: Decimal,
+    pub exposure: Decimal,
+    pub unrealized_profit: Decimal,
+}
+
+impl Equity {
+    pub fn total(&self) -> Decimal {
+        self.cash + self.exposure
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Wallet {
     balance: Decimal,
     parts: usize,
     borrowed: usize,
+    #[serde(skip)]
+    history: Vec<Equity>,
+    // Number of marks kept in the history, all by default.
+    #[serde(skip)]
+    limit: Option<usize>,
+    #[serde(skip)]
+    peak: Decimal,
+    #[serde(skip)]
+    max_drawdown: Decimal,
 }
 
 impl Wallet {
@@ -12,9 +38,19 @@ impl Wallet {
             balance,
             parts,
             borrowed: 0,
+            history: Vec::new(),
+            limit: None,
+            peak: Decimal::zero(),
+            max_drawdown: Decimal::zero(),
         }
     }
 
+    // Only keeps the given number of last marks, for traders that run indefinitely. The maximum
+    // drawdown still covers all of them.
+    pub fn keep(&mut self, limit: usize) {
+        self.limit = Some(limit);
+    }
+
     pub fn borrow(&mut self) -> Option<Decimal> {
         if self.borrowed < self.parts {
             let loan = self.balance / Decimal::from_usize(self.parts - self.borrowed).unwrap();
@@ -34,6 +70,54 @@ impl Wallet {
     pub fn update(&mut self, amount: Decimal) {
         self.balance = amount;
     }
+
+    // Records the value of the wallet given the current market value of all borrowed parts.
+    pub fn mark(&mut self, time: DateTime<Utc>, exposure: Decimal, unrealized_profit: Decimal) {
+        let equity = Equity {
+            time,
+            cash: self.balance,
+            exposure,
+            unrealized_profit,
+        };
+        self.peak = self.peak.max(equity.total());
+        if self.peak > Decimal::zero() {
+            self.max_drawdown = self
+                .max_drawdown
+                .max((self.peak - equity.total()) / self.peak);
+        }
+        self.history.push(equity);
+
+        // Drops the oldest marks in batches, so the history stays a slice.
+        if let Some(limit) = self.limit {
+            if self.history.len() >= 2 * limit.max(1) {
+                self.history.drain(..self.history.len() - limit);
+            }
+        }
+    }
+
+    pub fn balance(&self) -> Decimal {
+        self.balance
+    }
+
+    pub fn parts(&self) -> usize {
+        self.parts
+    }
+
+    pub fn borrowed(&self) -> usize {
+        self.borrowed
+    }
+
+    pub fn equity(&self) -> Option<&Equity> {
+        self.history.last()
+    }
+
+    pub fn history(&self) -> &[Equity] {
+        &self.history
+    }
+
+    pub fn max_drawdown(&self) -> Decimal {
+        self.max_drawdown
+    }
 }
 
 #[cfg(test)]
@@ -61,4 +145,32 @@ mod tests {
         wallet.put(Decimal::new(30, 0));
         assert_eq!(wallet.borrow(), Some(Decimal::new(30, 0)));
     }
+
+    #[test]
+    fn mark() {
+        let time = Utc::now();
+        let mut wallet = Wallet::new(Decimal::new(100, 0), 2);
+        assert_eq!(wallet.equity(), None);
+        wallet.borrow();
+        wallet.mark(time, Decimal::new(60, 0), Decimal::new(10, 0));
+        let equity = wallet.equity().unwrap();
+        assert_eq!(equity.cash, Decimal::new(50, 0));
+        assert_eq!(equity.total(), Decimal::new(110, 0));
+        assert_eq!(wallet.history().len(), 1);
+    }
+
+    #[test]
+    fn keep() {
+        let time = Utc::now();
+        let mut wallet = Wallet::new(Decimal::new(100, 0), 2);
+        wallet.keep(10);
+        wallet.mark(time, Decimal::zero(), Decimal::zero());
+        wallet.mark(time, Decimal::new(-50, 0), Decimal::zero());
+        for _ in 0..100 {
+            wallet.mark(time, Decimal::zero(), Decimal::zero());
+        }
+        assert!(wallet.history().len() < 20);
+        assert_eq!(wallet.equity().unwrap().total(), Decimal::new(100, 0));
+        assert_eq!(wallet.max_drawdown(), Decimal::new(5, 1));
+    }
 }